@@ -0,0 +1,472 @@
+//! Rewrite `expectations.yml` to match the observed behaviour of the tests.
+//!
+//! We edit the file as text, rather than re-serializing it, so that comments and
+//! formatting survive. Only the entries of tests that didn't behave as expected are
+//! rewritten, the entries of tests that no longer exist are removed and new tests are
+//! appended; everything else is left untouched.
+
+use crate::expectations::{ExpectedKind, ExpectedOutcome, ExpectedOutput};
+use crate::runner::TestOutcome;
+use crate::verify::ExerciseReport;
+use anyhow::Context;
+use pretty_assertions::StrComparison;
+use std::path::Path;
+
+/// The changes applied to an `expectations.yml` file.
+pub struct Blessed {
+    pub old: String,
+    pub new: String,
+    /// Tests whose expectations can't be derived from their outcome, because they didn't complete.
+    pub skipped: Vec<String>,
+}
+
+impl Blessed {
+    pub fn diff(&self) -> Option<String> {
+        (self.old != self.new).then(|| StrComparison::new(&self.old, &self.new).to_string())
+    }
+}
+
+/// Update the `expectations.yml` file in `exercise_dir` according to `report`.
+pub fn bless_exercise(
+    exercise_dir: &Path,
+    report: &ExerciseReport,
+) -> Result<Blessed, anyhow::Error> {
+    let path = exercise_dir.join("expectations.yml");
+    let old = fs_err::read_to_string(&path).context("Failed to read `expectations.yml` file")?;
+    let Changes {
+        edits,
+        additions,
+        skipped,
+    } = changes(report);
+    let new = rewrite(&old, &edits, &additions)?;
+    if new != old {
+        fs_err::write(&path, &new).context("Failed to write `expectations.yml` file")?;
+    }
+    Ok(Blessed { old, new, skipped })
+}
+
+/// What happens to an existing entry of the `tests` list.
+enum Edit {
+    Replace(ExpectedOutcome),
+    Remove,
+}
+
+/// An entry to append to the `tests` list.
+struct NewEntry {
+    name: String,
+    kind: Option<ExpectedKind>,
+    outcome: ExpectedOutcome,
+}
+
+struct Changes {
+    /// By position in the `tests` list, in order.
+    edits: Vec<(usize, Edit)>,
+    additions: Vec<NewEntry>,
+    /// The names of the tests that can't be blessed.
+    skipped: Vec<String>,
+}
+
+fn changes(report: &ExerciseReport) -> Changes {
+    let mut edits = Vec::new();
+    let mut additions = Vec::new();
+    let mut skipped = Vec::new();
+    for test in &report.tests {
+        if test.mismatch.is_none() {
+            continue;
+        }
+        let expects_panic = matches!(test.expected, ExpectedOutcome::Panic { .. });
+        match blessed_outcome(&test.actual, expects_panic) {
+            Some(outcome) => edits.push((test.entry, Edit::Replace(outcome))),
+            None => skipped.push(test.name.clone()),
+        }
+    }
+    for test in &report.missing {
+        edits.push((test.entry, Edit::Remove));
+    }
+    edits.sort_by_key(|(entry, _)| *entry);
+    for test in &report.unlisted {
+        // A new test is only expected to panic if it's a `#[should_panic]` test.
+        let expects_panic = matches!(
+            test.actual,
+            TestOutcome::Ok {
+                panic_message: Some(_)
+            }
+        );
+        match blessed_outcome(&test.actual, expects_panic) {
+            Some(outcome) => additions.push(NewEntry {
+                name: test.test_id.path.clone(),
+                kind: test.kind.clone(),
+                outcome,
+            }),
+            None => skipped.push(test.test_id.to_string()),
+        }
+    }
+    Changes {
+        edits,
+        additions,
+        skipped,
+    }
+}
+
+/// The expectation that matches the observed outcome of a test, if there is one.
+fn blessed_outcome(actual: &TestOutcome, expects_panic: bool) -> Option<ExpectedOutcome> {
+    let outcome = match actual {
+        TestOutcome::Ok {
+            panic_message: Some(panic_message),
+        }
+        | TestOutcome::Failed {
+            panic_message: Some(panic_message),
+            ..
+        } if expects_panic => ExpectedOutcome::Panic {
+            message: panic_message.to_owned(),
+        },
+        TestOutcome::Ok { .. } => ExpectedOutcome::Success,
+        TestOutcome::Failed { clean_stdout, .. } => ExpectedOutcome::Failure {
+            output: ExpectedOutput::Exact(clean_stdout.to_owned()),
+        },
+        TestOutcome::Ignored => ExpectedOutcome::Ignored,
+        TestOutcome::Timeout | TestOutcome::Interrupted => return None,
+    };
+    Some(outcome)
+}
+
+/// Apply `edits` to the entries of the `tests` list, then append `additions` to it.
+fn rewrite(
+    raw: &str,
+    edits: &[(usize, Edit)],
+    additions: &[NewEntry],
+) -> Result<String, anyhow::Error> {
+    if edits.is_empty() && additions.is_empty() {
+        return Ok(raw.to_owned());
+    }
+    let mut lines: Vec<&str> = raw.lines().collect();
+    // The list can be omitted when the tests are expected not to compile.
+    if !additions.is_empty() && !lines.iter().any(|line| line.trim_end() == "tests:") {
+        lines.push("tests:");
+    }
+    let (tests_line, entries) = find_entries(&lines)?;
+    let mut out: Vec<String> = Vec::with_capacity(lines.len());
+    let mut cursor = 0;
+    for (i, edit) in edits {
+        let entry = entries
+            .get(*i)
+            .context("`expectations.yml` has fewer entries than expected")?;
+        out.extend(lines[cursor..entry.start].iter().map(|l| l.to_string()));
+        if let Edit::Replace(outcome) = edit {
+            out.extend(render_entry(
+                &lines[entry.start..entry.end],
+                entry.indent,
+                outcome,
+            ));
+        }
+        cursor = entry.end;
+    }
+    if !additions.is_empty() {
+        let end = entries.last().map_or(tests_line + 1, |entry| entry.end);
+        let indent = entries.first().map_or(2, |entry| entry.indent);
+        out.extend(lines[cursor..end].iter().map(|l| l.to_string()));
+        for addition in additions {
+            out.extend(render_new_entry(indent, addition));
+        }
+        cursor = end;
+    }
+    out.extend(lines[cursor..].iter().map(|l| l.to_string()));
+    let mut rewritten = out.join("\n");
+    if raw.ends_with('\n') {
+        rewritten.push('\n');
+    }
+    Ok(rewritten)
+}
+
+/// The lines spanned by an entry of the `tests` list.
+struct Entry {
+    start: usize,
+    /// Exclusive. Trailing blank lines and comments are not part of the entry.
+    end: usize,
+    /// The indentation of the `-` that opens the entry.
+    indent: usize,
+}
+
+/// The line of the `tests` key, and the entries of the list.
+fn find_entries(lines: &[&str]) -> Result<(usize, Vec<Entry>), anyhow::Error> {
+    let tests_line = lines
+        .iter()
+        .position(|line| line.trim_end() == "tests:")
+        .context("`expectations.yml` doesn't have a top-level `tests` key")?;
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut item_indent = None;
+    for (i, line) in lines.iter().enumerate().skip(tests_line + 1) {
+        let trimmed = line.trim_start();
+        let indent = line.len() - trimmed.len();
+        if trimmed.is_empty() {
+            continue;
+        }
+        if trimmed.starts_with('#') && item_indent.is_none_or(|item| indent <= item + 2) {
+            continue;
+        }
+        if trimmed.starts_with("- ") && item_indent.is_none_or(|item| indent == item) {
+            item_indent = Some(indent);
+            entries.push(Entry {
+                start: i,
+                end: i + 1,
+                indent,
+            });
+        } else if item_indent.is_some_and(|item| indent > item) {
+            entries.last_mut().expect("An entry has been opened").end = i + 1;
+        } else {
+            // Another top-level key: the list is over.
+            break;
+        }
+    }
+    Ok((tests_line, entries))
+}
+
+/// Render a new version of an entry, reusing its `name` line and the keys
+/// that don't describe the outcome (`kind`, `timeout_secs`).
+fn render_entry(old_lines: &[&str], indent: usize, outcome: &ExpectedOutcome) -> Vec<String> {
+    let pad = " ".repeat(indent);
+    let name_line = old_lines
+        .iter()
+        .map(|line| line.trim())
+        .find_map(|line| line.trim_start_matches("- ").strip_prefix("name:"))
+        .unwrap_or_default()
+        .trim();
+    let mut lines = vec![format!("{pad}- name: {name_line}")];
+    let key = |key: &str, value: &str| format!("{pad}  {key}: {value}");
+    // Only the keys of the entry itself, not the content of its block scalars.
+    let entry_keys = old_lines.iter().enumerate().filter_map(|(i, line)| {
+        let trimmed = line.trim_start();
+        match i {
+            0 => trimmed.strip_prefix("- "),
+            _ if line.len() - trimmed.len() == indent + 2 => Some(trimmed),
+            _ => None,
+        }
+    });
+    for line in entry_keys {
+        for kept in ["kind:", "timeout_secs:"] {
+            if let Some(value) = line.strip_prefix(kept) {
+                lines.push(key(&kept[..kept.len() - 1], value.trim()));
+            }
+        }
+    }
+    lines.extend(render_outcome(&pad, outcome));
+    lines
+}
+
+/// Render the entry of a test that wasn't listed in `expectations.yml`.
+fn render_new_entry(indent: usize, entry: &NewEntry) -> Vec<String> {
+    let pad = " ".repeat(indent);
+    // JSON strings are valid double-quoted YAML scalars.
+    let quote =
+        |value: &str| serde_json::to_string(value).expect("Strings can always be serialized");
+    let mut lines = vec![format!("{pad}- name: {}", quote(&entry.name))];
+    if let Some(kind) = &entry.kind {
+        lines.push(format!("{pad}  kind: {}", quote(&kind.to_string())));
+    }
+    lines.extend(render_outcome(&pad, &entry.outcome));
+    lines
+}
+
+/// Render the keys that describe `outcome`.
+fn render_outcome(pad: &str, outcome: &ExpectedOutcome) -> Vec<String> {
+    let key = |key: &str, value: &str| format!("{pad}  {key}: {value}");
+    let mut lines = Vec::new();
+    match outcome {
+        ExpectedOutcome::Success => lines.push(key("expected_outcome", "\"success\"")),
+        ExpectedOutcome::Ignored => lines.push(key("expected_outcome", "\"ignored\"")),
+        ExpectedOutcome::Failure { output } => {
+            lines.push(key("expected_outcome", "\"failure\""));
+            lines.extend(render_string(pad, "expected_output", output.as_str()));
+        }
+        ExpectedOutcome::Panic { message } => {
+            lines.push(key("expected_outcome", "\"panic\""));
+            lines.extend(render_string(pad, "message", message));
+        }
+    }
+    lines
+}
+
+/// Render a string value: a double-quoted scalar for single lines, a block scalar otherwise.
+fn render_string(pad: &str, key: &str, value: &str) -> Vec<String> {
+    let representable_as_block = value.contains('\n')
+        && !value.ends_with('\n')
+        && !value.starts_with('\n')
+        && !value
+            .chars()
+            .any(|c| c.is_control() && c != '\n' && c != '\t');
+    if !representable_as_block {
+        // JSON strings are valid double-quoted YAML scalars.
+        let quoted = serde_json::to_string(value).expect("Strings can always be serialized");
+        return vec![format!("{pad}  {key}: {quoted}")];
+    }
+    // An explicit indentation indicator is needed if the first line starts with a space.
+    let indicator = if value.starts_with(' ') { "2" } else { "" };
+    let mut lines = vec![format!("{pad}  {key}: |{indicator}-")];
+    for line in value.lines() {
+        if line.is_empty() {
+            lines.push(String::new());
+        } else {
+            lines.push(format!("{pad}    {line}"));
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::TestId;
+    use crate::verify::{Mismatch, MissingTest, TestReport, UnlistedTest};
+
+    const RAW: &str = r#"# Expectations for the `eq` exercise
+tests:
+  - name: "failed_eq"
+    expected_outcome: "failure"
+    expected_output: |-
+      Value of: x
+      # not a comment
+  # The next test is flaky
+  - name: "happy"
+    kind: "integration:cli"
+    expected_outcome: "success"
+"#;
+
+    #[test]
+    fn only_the_replaced_entries_are_rewritten() {
+        let edits = [(
+            0,
+            Edit::Replace(ExpectedOutcome::Failure {
+                output: ExpectedOutput::Exact("Value of: y\n  which is 2".into()),
+            }),
+        )];
+        let expected = r#"# Expectations for the `eq` exercise
+tests:
+  - name: "failed_eq"
+    expected_outcome: "failure"
+    expected_output: |-
+      Value of: y
+        which is 2
+  # The next test is flaky
+  - name: "happy"
+    kind: "integration:cli"
+    expected_outcome: "success"
+"#;
+        assert_eq!(rewrite(RAW, &edits, &[]).unwrap(), expected);
+    }
+
+    #[test]
+    fn single_line_values_are_quoted() {
+        let edits = [(
+            1,
+            Edit::Replace(ExpectedOutcome::Panic {
+                message: "Panic \"#1\"".into(),
+            }),
+        )];
+        let rewritten = rewrite(RAW, &edits, &[]).unwrap();
+        assert!(rewritten.ends_with(
+            "  - name: \"happy\"\n    kind: \"integration:cli\"\n    expected_outcome: \"panic\"\n    message: \"Panic \\\"#1\\\"\"\n"
+        ));
+    }
+
+    #[test]
+    fn filtered_reports_rewrite_the_entries_they_refer_to() {
+        // Only `happy`, the second entry, was selected: it's the first test of the report.
+        let report = ExerciseReport {
+            tests: vec![TestReport {
+                name: "happy".into(),
+                entry: 1,
+                test_id: TestId {
+                    binary: None,
+                    path: "tests::happy".into(),
+                },
+                expected: ExpectedOutcome::Success,
+                actual: TestOutcome::Ignored,
+                mismatch: Some(Mismatch::UnexpectedIgnore),
+            }],
+            steps: Vec::new(),
+            killed: None,
+            compile: None,
+            harness: None,
+            unlisted: Vec::new(),
+            missing: Vec::new(),
+        };
+        let changes = changes(&report);
+        assert!(changes.skipped.is_empty());
+        let rewritten = rewrite(RAW, &changes.edits, &changes.additions).unwrap();
+        assert!(rewritten.contains("  - name: \"failed_eq\"\n    expected_outcome: \"failure\"\n"));
+        assert!(rewritten.ends_with(
+            "  - name: \"happy\"\n    kind: \"integration:cli\"\n    expected_outcome: \"ignored\"\n"
+        ));
+    }
+
+    #[test]
+    fn stale_entries_are_removed_and_new_tests_appended() {
+        let report = ExerciseReport {
+            tests: Vec::new(),
+            steps: Vec::new(),
+            killed: None,
+            compile: None,
+            harness: None,
+            unlisted: vec![
+                UnlistedTest {
+                    test_id: TestId {
+                        binary: None,
+                        path: "tests::renamed".into(),
+                    },
+                    kind: None,
+                    actual: TestOutcome::Ok {
+                        panic_message: None,
+                    },
+                },
+                UnlistedTest {
+                    test_id: TestId {
+                        binary: None,
+                        path: "tests::boom".into(),
+                    },
+                    kind: Some(ExpectedKind::Unit),
+                    actual: TestOutcome::Ok {
+                        panic_message: Some("Boom".into()),
+                    },
+                },
+            ],
+            missing: vec![MissingTest {
+                name: "failed_eq".into(),
+                kind: None,
+                entry: 0,
+            }],
+        };
+        let changes = changes(&report);
+        assert!(changes.skipped.is_empty());
+        let expected = r#"# Expectations for the `eq` exercise
+tests:
+  # The next test is flaky
+  - name: "happy"
+    kind: "integration:cli"
+    expected_outcome: "success"
+  - name: "tests::renamed"
+    expected_outcome: "success"
+  - name: "tests::boom"
+    kind: "unit"
+    expected_outcome: "panic"
+    message: "Boom"
+"#;
+        assert_eq!(
+            rewrite(RAW, &changes.edits, &changes.additions).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn new_tests_can_be_added_to_an_expected_compile_error() {
+        let raw = "expected_compile_error:\n  message: \"mismatched types\"\n";
+        let additions = [NewEntry {
+            name: "happy".into(),
+            kind: None,
+            outcome: ExpectedOutcome::Success,
+        }];
+        assert_eq!(
+            rewrite(raw, &[], &additions).unwrap(),
+            "expected_compile_error:\n  message: \"mismatched types\"\ntests:\n  - name: \"happy\"\n    expected_outcome: \"success\"\n"
+        );
+    }
+}
@@ -0,0 +1,159 @@
+//! Compiler diagnostics and build artifacts, as reported by `cargo test --message-format json`.
+//!
+//! With that option, cargo prints one JSON object per line on `stdout` for each
+//! message it receives from the compiler, interleaved with the output of the test binaries.
+
+use std::path::{Path, PathBuf};
+
+/// An error reported by the compiler while building the tests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompileError {
+    /// The target that failed to compile, e.g. the crate name for the library.
+    pub target: String,
+    /// The main message of the error, e.g. `mismatched types`.
+    pub message: String,
+    /// The error code, e.g. `E0308`, if there is one.
+    pub code: Option<String>,
+    /// Where the error is, according to its primary span.
+    pub location: Option<Location>,
+    /// The error as the compiler would print it on the terminal.
+    pub rendered: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    /// The path of the file, relative to the workspace root.
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl CompileError {
+    /// Whether the error is in `file`, which can be a trailing part of the path
+    /// (e.g. `src/lib.rs`).
+    pub fn is_in(&self, file: &str) -> bool {
+        self.location
+            .as_ref()
+            .is_some_and(|location| Path::new(&location.file).ends_with(file))
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct CargoMessage {
+    reason: String,
+    target: Option<CargoTarget>,
+    message: Option<CompilerMessage>,
+    /// Set for `compiler-artifact` messages about executables.
+    executable: Option<PathBuf>,
+}
+
+#[derive(serde::Deserialize)]
+struct CargoTarget {
+    name: String,
+    #[serde(default)]
+    kind: Vec<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct CompilerMessage {
+    message: String,
+    level: String,
+    code: Option<ErrorCode>,
+    #[serde(default)]
+    spans: Vec<Span>,
+    rendered: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct ErrorCode {
+    code: String,
+}
+
+#[derive(serde::Deserialize)]
+struct Span {
+    file_name: String,
+    line_start: usize,
+    column_start: usize,
+    is_primary: bool,
+}
+
+/// Collect the compile errors from the output of `cargo test --message-format json`.
+///
+/// Warnings, notes and the final `aborting due to N previous errors` summary are skipped.
+pub fn parse_compile_errors(output: &str) -> Vec<CompileError> {
+    output
+        .lines()
+        .filter(|line| line.starts_with("{\"reason\":"))
+        .filter_map(|line| serde_json::from_str::<CargoMessage>(line).ok())
+        .filter(|msg| msg.reason == "compiler-message")
+        .filter_map(|msg| {
+            let message = msg.message?;
+            if message.level != "error" || message.spans.is_empty() && message.code.is_none() {
+                return None;
+            }
+            let location = message
+                .spans
+                .iter()
+                .find(|span| span.is_primary)
+                .map(|span| Location {
+                    file: span.file_name.clone(),
+                    line: span.line_start,
+                    column: span.column_start,
+                });
+            Some(CompileError {
+                target: msg.target.map(|target| target.name).unwrap_or_default(),
+                message: message.message,
+                code: message.code.map(|code| code.code),
+                location,
+                rendered: message.rendered.unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+/// The path of the executable built for the test target named `target`, if any.
+pub fn test_executable(output: &str, target: &str) -> Option<PathBuf> {
+    output
+        .lines()
+        .filter(|line| line.starts_with("{\"reason\":"))
+        .filter_map(|line| serde_json::from_str::<CargoMessage>(line).ok())
+        .filter(|msg| msg.reason == "compiler-artifact")
+        .filter(|msg| {
+            msg.target
+                .as_ref()
+                .is_some_and(|t| t.name == target && t.kind.iter().any(|kind| kind == "test"))
+        })
+        .find_map(|msg| msg.executable)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compile_errors_are_extracted_from_cargo_messages() {
+        let output = r#"   Compiling keep_or_add v0.1.0 (/workshop/exercises/08_macros/02_test)
+{"reason":"compiler-message","package_id":"keep_or_add@0.1.0","target":{"name":"keep_or_add"},"message":{"message":"unused variable: `y`","level":"warning","code":null,"spans":[],"rendered":"warning: unused variable"}}
+{"reason":"compiler-message","package_id":"keep_or_add@0.1.0","target":{"name":"keep_or_add"},"message":{"message":"mismatched types","level":"error","code":{"code":"E0308","explanation":"..."},"spans":[{"file_name":"exercises/08_macros/02_test/src/lib.rs","line_start":22,"column_start":16,"is_primary":false},{"file_name":"exercises/08_macros/02_test/src/lib.rs","line_start":22,"column_start":22,"is_primary":true}],"rendered":"error[E0308]: mismatched types\n"}}
+{"reason":"compiler-message","package_id":"keep_or_add@0.1.0","target":{"name":"keep_or_add"},"message":{"message":"aborting due to 1 previous error","level":"error","code":null,"spans":[],"rendered":"error: aborting due to 1 previous error\n"}}
+{"reason":"build-finished","success":false}
+error: could not compile `keep_or_add` (lib) due to 1 previous error
+"#;
+        let errors = parse_compile_errors(output);
+        assert_eq!(errors.len(), 1);
+        let error = &errors[0];
+        assert_eq!(error.target, "keep_or_add");
+        assert_eq!(error.message, "mismatched types");
+        assert_eq!(error.code.as_deref(), Some("E0308"));
+        assert_eq!(
+            error.location,
+            Some(Location {
+                file: "exercises/08_macros/02_test/src/lib.rs".into(),
+                line: 22,
+                column: 22,
+            })
+        );
+        assert!(error.is_in("src/lib.rs"));
+        assert!(!error.is_in("lib.rs/src"));
+    }
+}
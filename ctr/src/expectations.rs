@@ -0,0 +1,328 @@
+use crate::diagnostics::CompileError;
+use crate::libtest::{TestBinary, TestKind};
+use crate::lint::lint;
+use crate::normalize::Normalizer;
+use anyhow::Context;
+use std::fmt;
+use std::path::Path;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct Expectations {
+    /// How to normalise the output of the tests before checking it.
+    #[serde(default)]
+    pub normalize: Vec<Normalizer>,
+    /// How long each test can run, in seconds. Overrides `--test-timeout`.
+    ///
+    /// Like `--test-timeout`, it's only enforced per test with the nightly backend.
+    pub timeout_secs: Option<u64>,
+    /// The tests are expected not to compile, e.g. because a macro must reject its input.
+    pub expected_compile_error: Option<ExpectedCompileError>,
+    /// Judge a custom test harness by its observable behaviour, rather than by the
+    /// outcome of each test. Can't be combined with `tests`.
+    pub harness: Option<HarnessExpectations>,
+    /// Can be omitted if the tests are expected not to compile.
+    #[serde(default)]
+    pub tests: Vec<TestExpectation>,
+}
+
+impl Expectations {
+    /// Load the `expectations.yml` file in the given exercise directory.
+    pub fn load(exercise_dir: &Path) -> Result<Self, anyhow::Error> {
+        let expectations_filepath = exercise_dir.join("expectations.yml");
+        let raw_expectations = fs_err::read_to_string(expectations_filepath)
+            .context("Failed to read `expectations.yml` file")?;
+        // `serde` silently ignores misspelled keys in flattened entries, so we validate
+        // the file first: it also gives better error messages, with a line number.
+        let diagnostics = lint(&raw_expectations);
+        if !diagnostics.is_empty() {
+            anyhow::bail!(
+                "`expectations.yml` is invalid:\n{}",
+                diagnostics
+                    .iter()
+                    .map(|diagnostic| format!("    {}", diagnostic))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            );
+        }
+        serde_yaml::from_str(&raw_expectations).context("Failed to parse `expectations.yml` file")
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct TestExpectation {
+    pub name: String,
+    /// Which kind of test binary the test lives in, to tell apart tests that share a name.
+    pub kind: Option<ExpectedKind>,
+    /// How long this test can run, in seconds.
+    pub timeout_secs: Option<u64>,
+    #[serde(flatten)]
+    pub outcome: ExpectedOutcome,
+}
+
+/// The expected behaviour of a test target with `harness = false`.
+///
+/// ```yaml
+/// harness:
+///   target: "exercise"
+///   expected_exit_code: 101
+///   listed_tests: ["happy_test", "sad_test"]
+///   stdout:
+///     expected_output_contains: "test result: FAILED"
+/// ```
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct HarnessExpectations {
+    /// The name of the test target, as declared in `Cargo.toml`.
+    pub target: String,
+    #[serde(default)]
+    pub expected_exit_code: i32,
+    /// The names of the tests that `<harness> --list` must print, in any order.
+    pub listed_tests: Option<Vec<String>>,
+    pub stdout: Option<ExpectedStream>,
+    pub stderr: Option<ExpectedStream>,
+}
+
+/// What a process must print on one of its output streams, using the same keys
+/// as the failure output of a test (`expected_output`, `expected_output_contains`, ...).
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct ExpectedStream {
+    #[serde(flatten)]
+    pub output: ExpectedOutput,
+}
+
+/// The kind of test binary a test lives in: `unit`, `integration:<binary>` or `doc`.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+#[serde(try_from = "String")]
+pub enum ExpectedKind {
+    Unit,
+    /// An integration test target, e.g. `integration:cli` for `tests/cli.rs`.
+    Integration(String),
+    Doc,
+}
+
+impl ExpectedKind {
+    /// The kind that selects `binary`.
+    pub fn of(binary: &TestBinary) -> Self {
+        match binary.kind {
+            TestKind::Unit => ExpectedKind::Unit,
+            TestKind::Integration => ExpectedKind::Integration(binary.name.clone()),
+            TestKind::Doc => ExpectedKind::Doc,
+        }
+    }
+
+    pub fn matches(&self, binary: Option<&TestBinary>) -> bool {
+        let Some(binary) = binary else {
+            return false;
+        };
+        match self {
+            ExpectedKind::Unit => binary.kind == TestKind::Unit,
+            ExpectedKind::Integration(name) => {
+                binary.kind == TestKind::Integration && &binary.name == name
+            }
+            ExpectedKind::Doc => binary.kind == TestKind::Doc,
+        }
+    }
+}
+
+impl fmt::Display for ExpectedKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExpectedKind::Unit => write!(f, "unit"),
+            ExpectedKind::Integration(binary) => write!(f, "integration:{}", binary),
+            ExpectedKind::Doc => write!(f, "doc"),
+        }
+    }
+}
+
+impl TryFrom<String> for ExpectedKind {
+    type Error = String;
+
+    fn try_from(kind: String) -> Result<Self, Self::Error> {
+        match kind.as_str() {
+            "unit" => Ok(ExpectedKind::Unit),
+            "doc" => Ok(ExpectedKind::Doc),
+            _ => match kind.strip_prefix("integration:") {
+                Some(binary) if !binary.is_empty() => {
+                    Ok(ExpectedKind::Integration(binary.to_owned()))
+                }
+                _ => Err(format!(
+                    "Unknown test kind `{}`: expected `unit`, `integration:<binary>` or `doc`",
+                    kind
+                )),
+            },
+        }
+    }
+}
+
+/// A compile error that must be among the ones reported by the compiler.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct ExpectedCompileError {
+    /// The main message of the error, e.g. `mismatched types`.
+    /// `[..]` can be used as a wildcard, like in `expected_output`.
+    pub message: String,
+    /// The file the error points at, relative to the exercise directory.
+    pub file: Option<String>,
+    pub line: Option<usize>,
+}
+
+impl ExpectedCompileError {
+    pub fn matches(&self, error: &CompileError) -> bool {
+        matches_with_wildcards(&self.message, &error.message)
+            && self.file.as_deref().is_none_or(|file| error.is_in(file))
+            && self.line.is_none_or(|line| {
+                error
+                    .location
+                    .as_ref()
+                    .is_some_and(|location| location.line == line)
+            })
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(tag = "expected_outcome")]
+#[serde(rename_all = "snake_case")]
+pub enum ExpectedOutcome {
+    Success,
+    Failure {
+        #[serde(flatten)]
+        output: ExpectedOutput,
+    },
+    /// The test is marked with `#[ignore]`.
+    Ignored,
+    /// The test panicked with the given message.
+    ///
+    /// This covers both `#[should_panic]` tests, which succeed, and tests that fail
+    /// because of a panic. `[..]` can be used as a wildcard, like in `expected_output`.
+    Panic {
+        message: String,
+    },
+}
+
+/// How the (cleaned up) output of a failed test is checked.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub enum ExpectedOutput {
+    /// The output must be equal to the expected one.
+    ///
+    /// `[..]` acts as a wildcard: it matches any sequence of characters within a line.
+    #[serde(rename = "expected_output")]
+    Exact(String),
+    /// The output must contain the given text.
+    #[serde(rename = "expected_output_contains")]
+    Contains(String),
+    /// The output must contain a match for the given regular expression.
+    /// Use `^` and `$` (with the `(?m)` flag, if needed) to anchor it.
+    #[serde(rename = "expected_output_regex")]
+    Regex(OutputRegex),
+}
+
+impl ExpectedOutput {
+    pub fn matches(&self, actual: &str) -> bool {
+        match self {
+            ExpectedOutput::Exact(expected) => matches_with_wildcards(expected, actual),
+            ExpectedOutput::Contains(expected) => actual.contains(expected.as_str()),
+            ExpectedOutput::Regex(regex) => regex.0.is_match(actual),
+        }
+    }
+
+    /// The expectation, as written in `expectations.yml`.
+    pub fn as_str(&self) -> &str {
+        match self {
+            ExpectedOutput::Exact(expected) | ExpectedOutput::Contains(expected) => expected,
+            ExpectedOutput::Regex(regex) => regex.0.as_str(),
+        }
+    }
+}
+
+const WILDCARD: &str = "[..]";
+
+/// Check if `actual` is equal to `expected`, where `[..]` in `expected` matches
+/// any sequence of characters within a line.
+pub fn matches_with_wildcards(expected: &str, actual: &str) -> bool {
+    if expected.contains(WILDCARD) {
+        wildcard_regex(expected).is_match(actual)
+    } else {
+        actual == expected
+    }
+}
+
+/// Turn an expected output with `[..]` wildcards into a regex that matches the whole output.
+fn wildcard_regex(expected: &str) -> regex::Regex {
+    let pattern = expected
+        .split(WILDCARD)
+        .map(regex::escape)
+        .collect::<Vec<_>>()
+        .join("[^\n]*");
+    regex::Regex::new(&format!(r"\A{}\z", pattern)).expect("Escaped patterns are valid regexes")
+}
+
+/// A regular expression, validated when `expectations.yml` is parsed.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(try_from = "String")]
+pub struct OutputRegex(regex::Regex);
+
+impl OutputRegex {
+    pub fn as_regex(&self) -> &regex::Regex {
+        &self.0
+    }
+}
+
+impl TryFrom<String> for OutputRegex {
+    type Error = regex::Error;
+
+    fn try_from(pattern: String) -> Result<Self, Self::Error> {
+        regex::Regex::new(&pattern).map(OutputRegex)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Expectations, ExpectedKind, ExpectedOutcome, ExpectedOutput};
+    use crate::libtest::{TestBinary, TestKind};
+
+    #[test]
+    fn wildcards_match_within_a_line() {
+        let expected = ExpectedOutput::Exact("Error at [..]:14:9\nDone".into());
+        assert!(expected.matches("Error at src/lib.rs:14:9\nDone"));
+        assert!(expected.matches("Error at :14:9\nDone"));
+        assert!(!expected.matches("Error at src\nlib.rs:14:9\nDone"));
+        assert!(!expected.matches("Error at src/lib.rs:14:9\nDone\n"));
+    }
+
+    #[test]
+    fn kinds_select_the_binary_a_test_lives_in() {
+        let kind = ExpectedKind::try_from("integration:cli".to_string()).unwrap();
+        let binary = |name: &str, kind| TestBinary {
+            name: name.into(),
+            kind,
+        };
+        assert!(kind.matches(Some(&binary("cli", TestKind::Integration))));
+        assert!(!kind.matches(Some(&binary("other", TestKind::Integration))));
+        assert!(!kind.matches(Some(&binary("cli", TestKind::Unit))));
+        assert!(!kind.matches(None));
+        assert_eq!(kind.to_string(), "integration:cli");
+        assert!(ExpectedKind::try_from("integration:".to_string()).is_err());
+        assert!(ExpectedKind::try_from("bench".to_string()).is_err());
+    }
+
+    #[test]
+    fn panics_are_expected_by_message() {
+        let raw = r#"
+tests:
+  - name: "panic_before"
+    expected_outcome: "panic"
+    message: "Panic #1"
+  - name: "should_panic"
+    expected_outcome: "panic"
+    message: "Index out of bounds: [..]"
+"#;
+        let expectations: Expectations = serde_yaml::from_str(raw).unwrap();
+        let messages: Vec<&str> = expectations
+            .tests
+            .iter()
+            .map(|test| match &test.outcome {
+                ExpectedOutcome::Panic { message } => message.as_str(),
+                outcome => panic!("Unexpected outcome: {:?}", outcome),
+            })
+            .collect();
+        assert_eq!(messages, ["Panic #1", "Index out of bounds: [..]"]);
+    }
+}
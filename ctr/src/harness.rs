@@ -0,0 +1,470 @@
+//! Verify a custom test harness (`harness = false`) by its observable behaviour.
+//!
+//! A hand-written harness doesn't necessarily speak libtest's output formats, so we don't
+//! look at individual tests. We build the test binary, run it directly and check its exit
+//! code and what it prints, as well as the tests it lists when invoked with `--list`.
+
+use crate::diagnostics::{parse_compile_errors, test_executable, CompileError};
+use crate::expectations::{ExpectedOutput, HarnessExpectations};
+use crate::normalize::Pipeline;
+use crate::runner::TestSelection;
+use crate::timeout::{kill_process_tree, TrackedTree};
+use anyhow::Context;
+use owo_colors::OwoColorize;
+use pretty_assertions::StrComparison;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+pub struct HarnessReport {
+    pub expected: HarnessExpectations,
+    /// `None` if the harness was terminated by a signal.
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    /// The tests printed by `<harness> --list`, sorted by name.
+    pub listed_tests: Vec<String>,
+    /// Why the harness was killed before completing, if it was.
+    pub killed: Option<String>,
+    pub mismatches: Vec<HarnessMismatch>,
+}
+
+/// The ways in which a harness can fail to meet its expectations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HarnessMismatch {
+    ExitCode,
+    Stdout,
+    Stderr,
+    ListedTests,
+}
+
+/// What happened when we tried to build and run the harness.
+pub enum HarnessOutcome {
+    /// The test target didn't compile.
+    CompileErrors(Vec<CompileError>),
+    Ran(Box<HarnessReport>),
+}
+
+/// Build the test target described by `expectations`, in `dir`, and check how it behaves.
+///
+/// The harness is run twice: once as `cargo test` would, once with `--list`.
+/// Each run must complete within `timeout`.
+pub fn verify_harness(
+    dir: &Path,
+    env: &BTreeMap<String, String>,
+    expectations: HarnessExpectations,
+    selection: &TestSelection,
+    timeout: Duration,
+    pipeline: &Pipeline,
+) -> Result<HarnessOutcome, anyhow::Error> {
+    let executable = match build(dir, env, selection, &expectations.target)? {
+        Ok(executable) => executable,
+        Err(errors) => return Ok(HarnessOutcome::CompileErrors(errors)),
+    };
+    let mut args: Vec<String> = selection.filter.iter().cloned().collect();
+    args.extend(selection.libtest_args.iter().cloned());
+    let run = run(&executable, dir, env, &args, timeout)?;
+    let listed_tests = if expectations.listed_tests.is_some() {
+        args.insert(0, "--list".into());
+        list_tests(&run_to_completion(&executable, dir, env, &args, timeout)?)
+    } else {
+        Vec::new()
+    };
+    let killed = run.killed.map(|limit| {
+        format!(
+            "`{}` didn't complete within {} seconds",
+            expectations.target,
+            limit.as_secs()
+        )
+    });
+
+    let stdout = pipeline.apply(&run.stdout);
+    let stderr = pipeline.apply(&run.stderr);
+    let mismatches = find_mismatches(
+        &expectations,
+        run.exit_code,
+        &stdout,
+        &stderr,
+        &listed_tests,
+    );
+    Ok(HarnessOutcome::Ran(Box::new(HarnessReport {
+        expected: expectations,
+        exit_code: run.exit_code,
+        stdout,
+        stderr,
+        listed_tests,
+        killed,
+        mismatches,
+    })))
+}
+
+/// Compare what the harness did with what `expectations.yml` says it should do.
+///
+/// `stdout` and `stderr` have been normalised already, `listed_tests` is sorted.
+fn find_mismatches(
+    expectations: &HarnessExpectations,
+    exit_code: Option<i32>,
+    stdout: &str,
+    stderr: &str,
+    listed_tests: &[String],
+) -> Vec<HarnessMismatch> {
+    let mut mismatches = Vec::new();
+    if exit_code != Some(expectations.expected_exit_code) {
+        mismatches.push(HarnessMismatch::ExitCode);
+    }
+    if expectations
+        .stdout
+        .as_ref()
+        .is_some_and(|expected| !expected.output.matches(stdout))
+    {
+        mismatches.push(HarnessMismatch::Stdout);
+    }
+    if expectations
+        .stderr
+        .as_ref()
+        .is_some_and(|expected| !expected.output.matches(stderr))
+    {
+        mismatches.push(HarnessMismatch::Stderr);
+    }
+    if let Some(expected) = &expectations.listed_tests {
+        let mut expected = expected.clone();
+        expected.sort();
+        if expected != listed_tests {
+            mismatches.push(HarnessMismatch::ListedTests);
+        }
+    }
+    mismatches
+}
+
+/// Build the test target with `cargo test --no-run` and return the path of its executable.
+fn build(
+    dir: &Path,
+    env: &BTreeMap<String, String>,
+    selection: &TestSelection,
+    target: &str,
+) -> Result<Result<PathBuf, Vec<CompileError>>, anyhow::Error> {
+    let output = Command::new("cargo")
+        .current_dir(dir)
+        .envs(env)
+        .args([
+            "test",
+            "--no-run",
+            "--message-format",
+            "json",
+            "--test",
+            target,
+        ])
+        .args(selection.build_args())
+        .output()
+        .context("Failed to run `cargo test --no-run`")?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let compile_errors = parse_compile_errors(&stdout);
+    if !compile_errors.is_empty() {
+        return Ok(Err(compile_errors));
+    }
+    match test_executable(&stdout, target) {
+        Some(executable) => Ok(Ok(executable)),
+        None => anyhow::bail!(
+            "Failed to build the `{}` test target.\nOutput:\n{}",
+            target,
+            textwrap::indent(&String::from_utf8_lossy(&output.stderr), "    ")
+        ),
+    }
+}
+
+struct HarnessRun {
+    exit_code: Option<i32>,
+    stdout: String,
+    stderr: String,
+    /// Set to the time limit if the harness had to be killed.
+    killed: Option<Duration>,
+}
+
+/// Run the harness like `cargo test` would: from the package directory, with
+/// `CARGO_MANIFEST_DIR` set.
+fn run(
+    executable: &Path,
+    dir: &Path,
+    env: &BTreeMap<String, String>,
+    args: &[String],
+    timeout: Duration,
+) -> Result<HarnessRun, anyhow::Error> {
+    let manifest_dir = fs_err::canonicalize(dir)?;
+    let mut command = Command::new(executable);
+    command
+        .current_dir(dir)
+        .envs(env)
+        .env("CARGO_MANIFEST_DIR", manifest_dir)
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    let mut child = command
+        .spawn()
+        .with_context(|| format!("Failed to run `{}`", executable.display()))?;
+    let _tracked = TrackedTree::new(&child);
+    let stdout = read_in_background(child.stdout.take().expect("stdout is piped"));
+    let stderr = read_in_background(child.stderr.take().expect("stderr is piped"));
+
+    let start = Instant::now();
+    let mut killed = None;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if start.elapsed() > timeout {
+            kill_process_tree(&mut child)?;
+            killed = Some(timeout);
+            break child.wait()?;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    };
+    Ok(HarnessRun {
+        exit_code: status.code(),
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+        killed,
+    })
+}
+
+/// Run the harness and return its `stdout`, as long as it completes successfully
+/// within `timeout`.
+fn run_to_completion(
+    executable: &Path,
+    dir: &Path,
+    env: &BTreeMap<String, String>,
+    args: &[String],
+    timeout: Duration,
+) -> Result<String, anyhow::Error> {
+    let run = run(executable, dir, env, args, timeout)?;
+    let command = format!("{} {}", executable.display(), args.join(" "));
+    if run.killed.is_some() {
+        anyhow::bail!(
+            "`{}` didn't complete within {} seconds",
+            command,
+            timeout.as_secs()
+        );
+    }
+    if run.exit_code != Some(0) {
+        let status = match run.exit_code {
+            Some(code) => format!("exit code {}", code),
+            None => "no exit code".to_string(),
+        };
+        anyhow::bail!(
+            "`{}` failed with {}.\nstderr:\n{}",
+            command,
+            status,
+            textwrap::indent(&run.stderr, "    ")
+        );
+    }
+    Ok(run.stdout)
+}
+
+fn read_in_background(mut pipe: impl Read + Send + 'static) -> std::thread::JoinHandle<String> {
+    std::thread::spawn(move || {
+        let mut buffer = Vec::new();
+        let _ = pipe.read_to_end(&mut buffer);
+        String::from_utf8_lossy(&buffer).into_owned()
+    })
+}
+
+/// Extract the test names from the output of `--list`, i.e. `<name>: test` lines.
+///
+/// Benchmarks (`<name>: bench`) are listed too, as `cargo test` runs them as tests.
+fn list_tests(stdout: &str) -> Vec<String> {
+    let mut tests: Vec<String> = stdout
+        .lines()
+        .filter_map(|line| {
+            line.strip_suffix(": test")
+                .or_else(|| line.strip_suffix(": bench"))
+        })
+        .map(|name| name.to_owned())
+        .collect();
+    tests.sort();
+    tests
+}
+
+impl HarnessReport {
+    pub fn passed(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+
+    pub fn render(&self, out: &mut String) {
+        let target = &self.expected.target;
+        let intro_msg = format!("🔘 Checking the `{}` harness against expectations", target);
+        writeln!(out, "{}", intro_msg.bold()).unwrap();
+        if let Some(reason) = &self.killed {
+            writeln!(out, "{}", format!("⏱️ {}", reason).bold().red()).unwrap();
+        }
+        for mismatch in &self.mismatches {
+            writeln!(out, "{}", self.mismatch_message(*mismatch).bold().red()).unwrap();
+            match mismatch {
+                HarnessMismatch::ExitCode => {
+                    writeln!(
+                        out,
+                        "{}\n{}\n{}\n{}",
+                        "stdout:".bold(),
+                        textwrap::indent(&self.stdout, "    "),
+                        "stderr:".bold(),
+                        textwrap::indent(&self.stderr, "    ")
+                    )
+                    .unwrap();
+                }
+                HarnessMismatch::Stdout => {
+                    let expected = self.expected.stdout.as_ref().map(|s| &s.output);
+                    render_output_mismatch(out, expected, &self.stdout);
+                }
+                HarnessMismatch::Stderr => {
+                    let expected = self.expected.stderr.as_ref().map(|s| &s.output);
+                    render_output_mismatch(out, expected, &self.stderr);
+                }
+                HarnessMismatch::ListedTests => {
+                    let expected = self.expected.listed_tests.as_deref().unwrap_or_default();
+                    writeln!(
+                        out,
+                        "Expected: {}\nActual:   {}",
+                        render_names(expected),
+                        render_names(&self.listed_tests)
+                    )
+                    .unwrap();
+                }
+            }
+        }
+    }
+
+    pub fn mismatch_message(&self, mismatch: HarnessMismatch) -> String {
+        let target = &self.expected.target;
+        match mismatch {
+            HarnessMismatch::ExitCode => {
+                let status = match self.exit_code {
+                    Some(code) => format!("exit code {}", code),
+                    None => "no exit code".to_string(),
+                };
+                format!(
+                    "`{}` exited with {}, but was expected to exit with code {}",
+                    target, status, self.expected.expected_exit_code
+                )
+            }
+            HarnessMismatch::Stdout => format!(
+                "The stdout of `{}` doesn't match the expected output from `expectations.yml`",
+                target
+            ),
+            HarnessMismatch::Stderr => format!(
+                "The stderr of `{}` doesn't match the expected output from `expectations.yml`",
+                target
+            ),
+            HarnessMismatch::ListedTests => format!(
+                "`{} --list` doesn't list the tests expected by `expectations.yml`",
+                target
+            ),
+        }
+    }
+}
+
+fn render_output_mismatch(out: &mut String, expected: Option<&ExpectedOutput>, actual: &str) {
+    match expected {
+        Some(ExpectedOutput::Exact(expected)) => {
+            writeln!(out, "{}", StrComparison::new(actual, expected.as_str())).unwrap();
+        }
+        Some(expected) => {
+            writeln!(
+                out,
+                "{}\n{}\n{}\n{}",
+                "Expected:".bold(),
+                textwrap::indent(expected.as_str(), "    "),
+                "Actual:".bold(),
+                textwrap::indent(actual, "    ")
+            )
+            .unwrap();
+        }
+        None => {}
+    }
+}
+
+fn render_names(names: &[String]) -> String {
+    if names.is_empty() {
+        return "(none)".into();
+    }
+    names
+        .iter()
+        .map(|name| format!("`{}`", name))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn listed_tests_are_extracted_and_sorted() {
+        let stdout = "sad_test: test\nhappy_test: test\nslow: bench\n\n3 tests, 1 benchmark\n";
+        assert_eq!(list_tests(stdout), ["happy_test", "sad_test", "slow"]);
+        assert!(list_tests("").is_empty());
+    }
+
+    #[test]
+    fn each_kind_of_mismatch_is_reported() {
+        let expectations: HarnessExpectations = serde_yaml::from_str(
+            r#"
+target: cli
+expected_exit_code: 101
+listed_tests: ["sad_test", "happy_test"]
+stdout:
+  expected_output_contains: "1 failed"
+stderr:
+  expected_output_regex: "^thread 'sad_test' panicked"
+"#,
+        )
+        .unwrap();
+        let listed_tests = ["happy_test".to_string(), "sad_test".to_string()];
+        let stdout = "1 passed, 1 failed";
+        let stderr = "thread 'sad_test' panicked at src/main.rs:3:5";
+
+        let mismatches = |exit_code, stdout, stderr, listed_tests: &[String]| {
+            find_mismatches(&expectations, exit_code, stdout, stderr, listed_tests)
+        };
+        assert!(mismatches(Some(101), stdout, stderr, &listed_tests).is_empty());
+        assert_eq!(
+            mismatches(Some(0), stdout, stderr, &listed_tests),
+            [HarnessMismatch::ExitCode]
+        );
+        assert_eq!(
+            mismatches(None, stdout, stderr, &listed_tests),
+            [HarnessMismatch::ExitCode]
+        );
+        assert_eq!(
+            mismatches(Some(101), "2 passed", "", &listed_tests[..1]),
+            [
+                HarnessMismatch::Stdout,
+                HarnessMismatch::Stderr,
+                HarnessMismatch::ListedTests
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn a_failing_list_run_reports_its_stderr() {
+        let args = [
+            "-c".to_string(),
+            "echo 'unknown flag' >&2; exit 2".to_string(),
+        ];
+        let error = run_to_completion(
+            Path::new("sh"),
+            Path::new("."),
+            &BTreeMap::new(),
+            &args,
+            Duration::from_secs(10),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("failed with exit code 2"), "{}", error);
+        assert!(error.contains("unknown flag"), "{}", error);
+    }
+}
@@ -0,0 +1,375 @@
+//! Parsers for the output of `libtest`, the default Rust test harness.
+//!
+//! Both parsers produce the same stream of [`LibtestMessage`]s, so that the rest of `ctr`
+//! doesn't need to care about which output format was used.
+
+use anyhow::Context;
+use std::collections::HashMap;
+
+/// Reference for the JSON output of `libtest`: https://github.com/rust-lang/rust/blob/master/library/test/src/formatters/json.rs
+///
+/// We only model events with `type` set to `test`. We ignore everything else.
+#[derive(serde::Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct LibtestMessage {
+    pub name: String,
+    /// The test binary that emitted the event, if `cargo` told us about it.
+    #[serde(skip)]
+    pub binary: Option<TestBinary>,
+    #[serde(flatten)]
+    pub event_data: TestEventData,
+}
+
+#[derive(serde::Deserialize, Debug)]
+#[serde(tag = "event")]
+#[serde(rename_all = "snake_case")]
+pub enum TestEventData {
+    Started,
+    Failed {
+        stdout: Option<String>,
+    },
+    /// `stdout` is only reported for successful tests when `--show-output` is set.
+    Ok {
+        stdout: Option<String>,
+    },
+    Ignored,
+    Timeout,
+}
+
+/// Parse the output of `cargo test -- -Z unstable-options --format json`.
+///
+/// The output is going to be a mix of JSON and non-JSON lines, so we need to
+/// filter out the non-JSON lines.
+/// For the JSON lines, we parse them and keep the ones with `type` set to `test`,
+/// indicating the outcome of a particular test.
+/// Non-JSON lines are still inspected to find out which test binary is running.
+pub fn parse_json_output(stdout: &str) -> Result<Vec<LibtestMessage>, anyhow::Error> {
+    let mut messages = Vec::new();
+    let mut binary = None;
+    for line in stdout.lines() {
+        let Ok(libtest_msg) = serde_json::from_str::<serde_json::Value>(line) else {
+            if let Some(b) = test_binary(line) {
+                binary = Some(b);
+            }
+            continue;
+        };
+        if !is_test_event(&libtest_msg) {
+            continue;
+        }
+        let mut libtest_msg = serde_json::from_value::<LibtestMessage>(libtest_msg)
+            .context("Failed to parse libtest message")?;
+        libtest_msg.binary = binary.clone();
+        messages.push(libtest_msg);
+    }
+    Ok(messages)
+}
+
+fn is_test_event(libtest_msg: &serde_json::Value) -> bool {
+    let Some(type_value) = libtest_msg.get("type") else {
+        return false;
+    };
+    let Some(type_value) = type_value.as_str() else {
+        return false;
+    };
+    type_value == "test"
+}
+
+/// A test binary run by `cargo test`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TestBinary {
+    /// The name of the binary: the crate name for unit tests and doctests,
+    /// the name of the target for integration tests.
+    pub name: String,
+    pub kind: TestKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum TestKind {
+    /// Tests in the library or in a binary target, under `src/`.
+    Unit,
+    /// Tests in their own target, e.g. under `tests/`, with or without the libtest harness.
+    Integration,
+    Doc,
+}
+
+/// `cargo test` announces each test binary before running it, on stderr:
+///
+/// - `Running unittests src/lib.rs (target/debug/deps/<binary>-<hash>)`
+/// - `Running tests/<binary>.rs (target/debug/deps/<binary>-<hash>)`
+/// - `Doc-tests <crate>`
+///
+/// If `line` is one of those announcements, return the binary it refers to.
+pub fn test_binary(line: &str) -> Option<TestBinary> {
+    let line = line.trim();
+    if let Some(crate_name) = line.strip_prefix("Doc-tests ") {
+        return Some(TestBinary {
+            name: crate_name.trim().to_owned(),
+            kind: TestKind::Doc,
+        });
+    }
+    let executable = line.strip_prefix("Running ")?;
+    let kind = if executable.starts_with("unittests ") {
+        TestKind::Unit
+    } else {
+        TestKind::Integration
+    };
+    let executable = match executable.rsplit_once(" (") {
+        Some((_, executable)) => executable.strip_suffix(')')?,
+        None => executable,
+    };
+    let file_name = std::path::Path::new(executable).file_stem()?.to_str()?;
+    let binary = match file_name.rsplit_once('-') {
+        Some((binary, _hash)) => binary,
+        None => file_name,
+    };
+    Some(TestBinary {
+        name: binary.to_owned(),
+        kind,
+    })
+}
+
+/// Parse the output of `cargo test -- --format pretty`, the format you get on stable.
+///
+/// We look at two kinds of lines:
+///
+/// - `test <name> ... <result>`, emitted when a test completes;
+/// - `---- <name> stdout ----`, which opens the block containing the captured output
+///   of a test. The block ends when the next one starts or when libtest prints
+///   the summary list of failed (`failures:`) or successful (`successes:`) tests.
+///   Successful tests only get a block when `--show-output` is set.
+///
+/// Custom harnesses built with `libtest-mimic` use the same format, with two small
+/// differences: test names are padded with spaces and the block header omits `stdout`.
+/// Their failure message is reshaped to match what they emit in JSON mode.
+pub fn parse_human_output(stdout: &str) -> Vec<LibtestMessage> {
+    let mut results = Vec::new();
+    let mut captured_outputs: HashMap<(Option<TestBinary>, String), String> = HashMap::new();
+    let mut current_block: Option<OutputBlock> = None;
+    let mut binary = None;
+    let mut pending = None;
+
+    for line in stdout.lines() {
+        // An output block is always closed by a summary list before the next
+        // binary starts, so we don't mistake captured output for a cargo announcement.
+        if current_block.is_none() {
+            if let Some(b) = test_binary(line) {
+                binary = Some(b);
+                continue;
+            }
+        }
+        if let Some(block) = OutputBlock::open(line, &binary) {
+            if let Some(previous) = current_block.replace(block) {
+                previous.close(&mut captured_outputs);
+            }
+            continue;
+        }
+        if let Some(block) = current_block.as_mut() {
+            if line == "failures:" || line == "successes:" {
+                current_block.take().unwrap().close(&mut captured_outputs);
+            } else {
+                block.output.push_str(line);
+                block.output.push('\n');
+            }
+            continue;
+        }
+        // Output that the harness doesn't capture (e.g. a panic in a custom harness) can be
+        // printed between `test <name> ... ` and the result, pushing the result to its own line.
+        if let Some(name) = pending.take() {
+            match TestResult::parse(line) {
+                Some(result) => {
+                    results.extend(result.into_message(name, &binary));
+                    continue;
+                }
+                None => pending = Some(name),
+            }
+        }
+        let Some(rest) = line.strip_prefix("test ") else {
+            continue;
+        };
+        if let Some((name, result)) = rest.rsplit_once(" ... ") {
+            let name = name.trim_end();
+            // `#[should_panic]` tests are listed as `test <name> - should panic ... <result>`
+            let name = name.strip_suffix(" - should panic").unwrap_or(name);
+            if result.trim().is_empty() {
+                // With a single test thread, this is printed when the test starts.
+                results.push(LibtestMessage {
+                    name: name.to_owned(),
+                    binary: binary.clone(),
+                    event_data: TestEventData::Started,
+                });
+                pending = Some(name.to_owned());
+            } else if let Some(result) = TestResult::parse(result) {
+                results.extend(result.into_message(name.to_owned(), &binary));
+            }
+        } else if let Some(name) = rest.strip_suffix(" seconds") {
+            // `test <name> has been running for over <n> seconds`
+            let Some((name, _)) = name.rsplit_once(" has been running for over ") else {
+                continue;
+            };
+            results.push(LibtestMessage {
+                name: name.to_owned(),
+                binary: binary.clone(),
+                event_data: TestEventData::Timeout,
+            });
+        }
+    }
+    if let Some(block) = current_block {
+        block.close(&mut captured_outputs);
+    }
+
+    for message in &mut results {
+        if let TestEventData::Failed { stdout } | TestEventData::Ok { stdout } =
+            &mut message.event_data
+        {
+            *stdout = captured_outputs.remove(&(message.binary.clone(), message.name.clone()));
+        }
+    }
+    results
+}
+
+/// The result of a test, as printed by the `pretty` formatter.
+enum TestResult {
+    Ok,
+    Failed,
+    /// `ignored` or `ignored, <reason>`
+    Ignored,
+    /// Benchmarks, which we don't verify.
+    Skipped,
+}
+
+impl TestResult {
+    fn parse(result: &str) -> Option<Self> {
+        let result = result.trim();
+        match result {
+            "ok" => Some(TestResult::Ok),
+            "FAILED" => Some(TestResult::Failed),
+            _ if result.starts_with("ignored") => Some(TestResult::Ignored),
+            _ if result.starts_with("bench:") => Some(TestResult::Skipped),
+            _ => None,
+        }
+    }
+
+    fn into_message(self, name: String, binary: &Option<TestBinary>) -> Option<LibtestMessage> {
+        let event_data = match self {
+            TestResult::Ok => TestEventData::Ok { stdout: None },
+            TestResult::Failed => TestEventData::Failed { stdout: None },
+            TestResult::Ignored => TestEventData::Ignored,
+            TestResult::Skipped => return None,
+        };
+        Some(LibtestMessage {
+            name,
+            binary: binary.clone(),
+            event_data,
+        })
+    }
+}
+
+/// The captured output of a test, as printed by the `pretty` formatter.
+struct OutputBlock {
+    name: String,
+    binary: Option<TestBinary>,
+    output: String,
+    libtest_mimic: bool,
+}
+
+impl OutputBlock {
+    /// Open a new block if `line` is a `---- <name> stdout ----` or `---- <name> ----` header.
+    fn open(line: &str, binary: &Option<TestBinary>) -> Option<Self> {
+        let name = line.strip_prefix("---- ")?.strip_suffix(" ----")?;
+        let (name, libtest_mimic) = match name.strip_suffix(" stdout") {
+            Some(name) => (name, false),
+            None => (name, true),
+        };
+        Some(Self {
+            name: name.to_owned(),
+            binary: binary.clone(),
+            output: String::new(),
+            libtest_mimic,
+        })
+    }
+
+    fn close(self, captured_outputs: &mut HashMap<(Option<TestBinary>, String), String>) {
+        let output = if self.libtest_mimic {
+            // Match what `libtest-mimic` puts in the `stdout` field of its JSON output,
+            // so that both backends agree on the outcome of the test.
+            format!("Error: \"{}\"\n", self.output.trim_end_matches('\n'))
+        } else {
+            self.output
+        };
+        captured_outputs.insert((self.binary, self.name), output);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HUMAN_OUTPUT: &str = "
+     Running unittests src/lib.rs (target/debug/deps/googletest_eq-c74c1447b6ae02ae)
+
+running 3 tests
+test tests::happy ... ok
+test tests::sad ... FAILED
+test tests::skipped ... ignored, not today
+
+failures:
+
+---- tests::sad stdout ----
+
+thread 'tests::sad' (7033) panicked at src/lib.rs:14:9:
+assertion failed
+
+failures:
+    tests::sad
+
+test result: FAILED. 1 passed; 1 failed; 1 ignored; 0 measured; 0 filtered out; finished in 0.00s
+";
+
+    #[test]
+    fn human_output_is_parsed_into_libtest_messages() {
+        let messages = parse_human_output(HUMAN_OUTPUT);
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0].name, "tests::happy");
+        assert_eq!(
+            messages[0].binary,
+            Some(TestBinary {
+                name: "googletest_eq".into(),
+                kind: TestKind::Unit
+            })
+        );
+        assert!(matches!(
+            messages[0].event_data,
+            TestEventData::Ok { stdout: None }
+        ));
+        assert_eq!(messages[1].name, "tests::sad");
+        let TestEventData::Failed { stdout } = &messages[1].event_data else {
+            panic!("Expected a failure, got {:?}", messages[1].event_data);
+        };
+        assert_eq!(
+            stdout.as_deref(),
+            Some("\nthread 'tests::sad' (7033) panicked at src/lib.rs:14:9:\nassertion failed\n\n")
+        );
+        assert!(matches!(messages[2].event_data, TestEventData::Ignored));
+    }
+
+    #[test]
+    fn json_output_is_parsed_into_libtest_messages() {
+        let output = r#"     Running unittests src/lib.rs (target/debug/deps/tempfile_intro-c74c1447b6ae02ae)
+{ "type": "suite", "event": "started", "test_count": 2 }
+{ "type": "test", "event": "started", "name": "tests::skipped" }
+{ "type": "test", "name": "tests::skipped", "event": "ignored", "message": "not today" }
+{ "type": "test", "name": "tests::should_panic", "event": "ok", "stdout": "panicked" }
+"#;
+        let messages = parse_json_output(output).unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(
+            messages[1].binary.as_ref().map(|b| b.name.as_str()),
+            Some("tempfile_intro")
+        );
+        assert!(matches!(messages[1].event_data, TestEventData::Ignored));
+        let TestEventData::Ok { stdout } = &messages[2].event_data else {
+            panic!("Expected a success, got {:?}", messages[2].event_data);
+        };
+        assert_eq!(stdout.as_deref(), Some("panicked"));
+    }
+}
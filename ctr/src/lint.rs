@@ -0,0 +1,407 @@
+//! Check `expectations.yml` files for mistakes, pointing at the line they are on.
+//!
+//! The structure is validated against the published JSON Schema
+//! (`ctr/expectations.schema.json`), which editors can use for completion too:
+//!
+//! ```yaml
+//! # yaml-language-server: $schema=../../../ctr/expectations.schema.json
+//! ```
+//!
+//! On top of that, we flag entries that are valid but almost certainly not what
+//! the author meant, e.g. the same test listed twice.
+
+use crate::workspace::{discover_exercises, display_name};
+use once_cell::sync::Lazy;
+use owo_colors::OwoColorize;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use yaml_rust2::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust2::scanner::Marker;
+
+/// The JSON Schema of `expectations.yml`.
+pub const SCHEMA: &str = include_str!("../expectations.schema.json");
+
+static VALIDATOR: Lazy<jsonschema::Validator> = Lazy::new(|| {
+    let schema = serde_json::from_str(SCHEMA).expect("The schema is valid JSON");
+    jsonschema::options()
+        .should_validate_formats(true)
+        .build(&schema)
+        .expect("The schema is a valid JSON Schema")
+});
+
+/// The keys that describe the output of a failed test.
+const OUTPUT_KEYS: [&str; 3] = [
+    "expected_output",
+    "expected_output_contains",
+    "expected_output_regex",
+];
+
+/// A problem found in an `expectations.yml` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// 1-based.
+    pub line: usize,
+    /// 1-based.
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+/// Check the contents of an `expectations.yml` file, returning the problems
+/// in the order they appear in the file.
+pub fn lint(source: &str) -> Vec<Diagnostic> {
+    let spans = match Spans::parse(source) {
+        Ok(spans) => spans,
+        Err(diagnostic) => return vec![diagnostic],
+    };
+    let document: serde_json::Value = match serde_yaml::from_str(source) {
+        Ok(document) => document,
+        Err(e) => {
+            let (line, column) = e
+                .location()
+                .map(|location| (location.line(), location.column()))
+                .unwrap_or((1, 1));
+            return vec![Diagnostic {
+                line,
+                column,
+                message: e.to_string(),
+            }];
+        }
+    };
+
+    let mut diagnostics = Vec::new();
+    for error in VALIDATOR.iter_errors(&document) {
+        let pointer = error.instance_path.to_string();
+        match &error.kind {
+            jsonschema::error::ValidationErrorKind::AdditionalProperties { unexpected } => {
+                for key in unexpected {
+                    diagnostics.push(spans.diagnostic(
+                        &format!("{}/{}", pointer, escape(key)),
+                        format!("Unknown key `{}`", key),
+                    ));
+                }
+            }
+            _ => diagnostics.push(spans.diagnostic(&pointer, explain(&error, &pointer))),
+        }
+    }
+    diagnostics.extend(lint_entries(&document, &spans));
+    diagnostics.sort_by_key(|diagnostic| (diagnostic.line, diagnostic.column));
+    diagnostics.dedup();
+    diagnostics
+}
+
+/// Rephrase the schema violations whose generic message doesn't say how to fix them.
+fn explain(error: &jsonschema::ValidationError, pointer: &str) -> String {
+    use jsonschema::error::ValidationErrorKind as Kind;
+
+    let segments: Vec<&str> = pointer.split('/').skip(1).collect();
+    match (segments.as_slice(), &error.kind) {
+        (["tests", _], Kind::OneOfNotValid | Kind::OneOfMultipleValid) => {
+            "A `failure` entry needs exactly one of `expected_output`, \
+            `expected_output_contains` or `expected_output_regex`"
+                .into()
+        }
+        (["tests", _, "kind"], Kind::Pattern { .. }) => {
+            "Unknown test kind: expected `unit`, `integration:<binary>` or `doc`".into()
+        }
+        (["normalize", _], Kind::OneOfNotValid) => "Unknown normalizer: expected `strip_ansi`, \
+            `redact_paths`, `redact_temp_dir`, `remove_timestamps`, `remove_addresses` \
+            or `replace: { pattern, with }`"
+            .into(),
+        (["harness", stream], Kind::MinProperties { .. } | Kind::MaxProperties { .. }) => {
+            format!(
+                "`{}` needs exactly one of `expected_output`, `expected_output_contains` \
+                or `expected_output_regex`",
+                stream
+            )
+        }
+        _ => error.to_string(),
+    }
+}
+
+/// The mistakes that the schema can't catch.
+fn lint_entries(document: &serde_json::Value, spans: &Spans) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let tests = document
+        .get("tests")
+        .and_then(|tests| tests.as_array())
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    if document.get("harness").is_some() && !tests.is_empty() {
+        diagnostics.push(
+            spans.diagnostic(
+                "/tests",
+                "`tests` can't be combined with `harness`: a custom harness is checked as a whole"
+                    .into(),
+            ),
+        );
+    }
+
+    let mut seen: HashMap<(&str, Option<&str>), usize> = HashMap::new();
+    for (i, test) in tests.iter().enumerate() {
+        let entry = format!("/tests/{}", i);
+        let Some(name) = test.get("name").and_then(|name| name.as_str()) else {
+            continue;
+        };
+        let kind = test.get("kind").and_then(|kind| kind.as_str());
+        if let Some(first) = seen.insert((name, kind), i) {
+            let first_line = spans.position(&format!("/tests/{}", first)).0;
+            diagnostics.push(spans.diagnostic(
+                &format!("{}/name", entry),
+                format!(
+                    "`{}` is listed more than once, first on line {}",
+                    name, first_line
+                ),
+            ));
+        }
+
+        let outcome = test.get("expected_outcome").and_then(|o| o.as_str());
+        for key in OUTPUT_KEYS {
+            let Some(value) = test.get(key) else {
+                continue;
+            };
+            let pointer = format!("{}/{}", entry, key);
+            if outcome != Some("failure") {
+                diagnostics.push(spans.diagnostic(
+                    &pointer,
+                    format!(
+                        "`{}` is only checked for `failure` entries, not for `{}` ones",
+                        key,
+                        outcome.unwrap_or_default()
+                    ),
+                ));
+            } else if value.as_str() == Some("") {
+                diagnostics.push(spans.diagnostic(&pointer, empty_output_message(key)));
+            }
+        }
+        if test.get("message").is_some() && outcome != Some("panic") {
+            diagnostics.push(spans.diagnostic(
+                &format!("{}/message", entry),
+                format!(
+                    "`message` is only checked for `panic` entries, not for `{}` ones",
+                    outcome.unwrap_or_default()
+                ),
+            ));
+        } else if test.get("message").and_then(|m| m.as_str()) == Some("") {
+            diagnostics.push(spans.diagnostic(
+                &format!("{}/message", entry),
+                "`message` is empty: use `[..]` to accept any panic message".into(),
+            ));
+        }
+    }
+    diagnostics
+}
+
+fn empty_output_message(key: &str) -> String {
+    match key {
+        "expected_output" => {
+            "`expected_output` is empty, but a failed test always prints something".into()
+        }
+        _ => format!(
+            "`{}` is empty, so it matches any output: add the text to look for",
+            key
+        ),
+    }
+}
+
+/// The position of every node of a YAML document, keyed by its JSON pointer
+/// (e.g. `/tests/0/name`). For map entries, it's the position of the key.
+struct Spans(HashMap<String, Marker>);
+
+impl Spans {
+    fn parse(source: &str) -> Result<Self, Diagnostic> {
+        let mut events = Events(Vec::new());
+        Parser::new_from_str(source)
+            .load(&mut events, false)
+            .map_err(|e| Diagnostic {
+                line: e.marker().line(),
+                column: e.marker().col() + 1,
+                message: e.info().to_owned(),
+            })?;
+        let mut spans = HashMap::new();
+        // Skip `StreamStart` and `DocumentStart`.
+        if events.0.len() > 2 {
+            walk(&events.0, 2, "", &mut spans);
+        }
+        Ok(Spans(spans))
+    }
+
+    /// The line and column of the node at `pointer`, or of its closest ancestor
+    /// if the node is missing (e.g. a required key).
+    fn position(&self, pointer: &str) -> (usize, usize) {
+        let mut pointer = pointer;
+        loop {
+            if let Some(marker) = self.0.get(pointer) {
+                return (marker.line(), marker.col() + 1);
+            }
+            match pointer.rsplit_once('/') {
+                Some((parent, _)) => pointer = parent,
+                None => return (1, 1),
+            }
+        }
+    }
+
+    fn diagnostic(&self, pointer: &str, message: String) -> Diagnostic {
+        let (line, column) = self.position(pointer);
+        Diagnostic {
+            line,
+            column,
+            message,
+        }
+    }
+}
+
+struct Events(Vec<(Event, Marker)>);
+
+impl MarkedEventReceiver for Events {
+    fn on_event(&mut self, event: Event, marker: Marker) {
+        self.0.push((event, marker));
+    }
+}
+
+/// Record the position of the node starting at `events[i]` and of its children,
+/// returning the index of the event that follows it.
+fn walk(
+    events: &[(Event, Marker)],
+    mut i: usize,
+    pointer: &str,
+    spans: &mut HashMap<String, Marker>,
+) -> usize {
+    match &events[i].0 {
+        Event::SequenceStart(..) => {
+            i += 1;
+            let mut index = 0;
+            while !matches!(events[i].0, Event::SequenceEnd) {
+                let child = format!("{}/{}", pointer, index);
+                // The start of a block mapping is reported after its first key:
+                // we point at the key instead.
+                let marker = match (&events[i].0, events.get(i + 1)) {
+                    (Event::MappingStart(..), Some((Event::Scalar(..), key_marker))) => *key_marker,
+                    _ => events[i].1,
+                };
+                spans.insert(child.clone(), marker);
+                i = walk(events, i, &child, spans);
+                index += 1;
+            }
+            i + 1
+        }
+        Event::MappingStart(..) => {
+            i += 1;
+            while !matches!(events[i].0, Event::MappingEnd) {
+                let child = match &events[i].0 {
+                    Event::Scalar(key, ..) => format!("{}/{}", pointer, escape(key)),
+                    // Complex keys can't be expressed as JSON pointers.
+                    _ => String::new(),
+                };
+                spans.insert(child.clone(), events[i].1);
+                i = walk(events, i, &child, spans);
+                i = walk(events, i, &child, spans);
+            }
+            i + 1
+        }
+        _ => i + 1,
+    }
+}
+
+/// Escape a key for use in a JSON pointer, as per RFC 6901.
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+/// `ctr lint`: check the `expectations.yml` file of every exercise in the workspace.
+pub fn lint_workspace(workspace_root: &Path) -> Result<(), anyhow::Error> {
+    let exercises = discover_exercises(workspace_root)?;
+    let mut n_problems = 0;
+    let mut n_files = 0;
+    for exercise in &exercises {
+        let path = exercise.join("expectations.yml");
+        let source = fs_err::read_to_string(&path)?;
+        let diagnostics = lint(&source);
+        if diagnostics.is_empty() {
+            continue;
+        }
+        n_files += 1;
+        n_problems += diagnostics.len();
+        let name = display_name(workspace_root, &path);
+        for diagnostic in diagnostics {
+            println!("{}:{}", name.bold(), diagnostic);
+        }
+    }
+    if n_problems > 0 {
+        anyhow::bail!(
+            "Found {} problems in {} out of {} `expectations.yml` files",
+            n_problems,
+            n_files,
+            exercises.len()
+        );
+    }
+    println!("✅ {} `expectations.yml` files checked", exercises.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::lint;
+
+    fn messages(source: &str) -> Vec<String> {
+        lint(source).iter().map(|d| d.to_string()).collect()
+    }
+
+    #[test]
+    fn valid_expectations_have_no_diagnostics() {
+        let source = r#"normalize:
+  - strip_ansi
+  - replace:
+      pattern: "took \\d+ms"
+      with: "took [..]ms"
+tests:
+  - name: "happy"
+    expected_outcome: "success"
+  - name: "sad"
+    kind: "integration:cli"
+    expected_outcome: "failure"
+    expected_output_contains: "boom"
+  - name: "sad"
+    kind: "unit"
+    expected_outcome: "panic"
+    message: "[..]"
+"#;
+        assert_eq!(messages(source), Vec::<String>::new());
+    }
+
+    #[test]
+    fn mistakes_are_reported_with_their_position() {
+        let source = r#"tests:
+  - name: "happy"
+    expected_outcome: "success"
+    expected_output: "unused"
+  - name: "sad"
+    expected_outcome: "failure"
+    expected_outpt: "boom"
+  - name: "happy"
+    expected_outcome: "failure"
+    expected_output_contains: ""
+  - name: "boom"
+    expected_outcome: "failure"
+    expected_output: "Boom"
+    message: "Boom"
+"#;
+        assert_eq!(
+            messages(source),
+            [
+                "4:5: `expected_output` is only checked for `failure` entries, not for `success` ones",
+                "5:5: A `failure` entry needs exactly one of `expected_output`, `expected_output_contains` or `expected_output_regex`",
+                "7:5: Unknown key `expected_outpt`",
+                "8:5: `happy` is listed more than once, first on line 2",
+                "10:5: `expected_output_contains` is empty, so it matches any output: add the text to look for",
+                "14:5: `message` is only checked for `panic` entries, not for `failure` ones",
+            ]
+        );
+    }
+}
@@ -1,32 +1,109 @@
 use anyhow::Context;
-use once_cell::sync::Lazy;
-use owo_colors::OwoColorize;
-use pretty_assertions::StrComparison;
-use std::collections::{HashMap, HashSet};
-use std::path::PathBuf;
-
-#[derive(Debug, serde::Deserialize)]
-struct Expectations {
-    tests: Vec<TestExpectation>,
-}
-
-#[derive(Debug, serde::Deserialize)]
-struct TestExpectation {
-    name: String,
-    #[serde(flatten)]
-    outcome: ExpectedOutcome,
+use clap::{Parser, Subcommand};
+use report::ReportArgs;
+use runner::{Backend, TestSelection};
+use std::path::{Path, PathBuf};
+use timeout::Timeouts;
+use verify::ExerciseRun;
+
+mod bless;
+mod diagnostics;
+mod expectations;
+mod harness;
+mod libtest;
+mod lint;
+mod normalize;
+mod progress;
+mod report;
+mod runner;
+mod stability;
+mod steps;
+mod timeout;
+mod verify;
+mod watch;
+mod workspace;
+
+/// Verify the tests of the exercise in the current directory against `expectations.yml`.
+#[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// How to collect test outcomes from `cargo test`.
+    #[arg(long, value_enum, default_value_t = Backend::Auto, global = true)]
+    backend: Backend,
+    /// Verify every exercise in the workspace, rather than the one in the current directory.
+    #[arg(long)]
+    workspace: bool,
+    /// How many exercises to verify in parallel, in `--workspace` mode.
+    #[arg(long, short, default_value_t = 1, requires = "workspace")]
+    jobs: usize,
+    /// Rewrite `expectations.yml` to match the actual outcome of the tests that
+    /// didn't behave as expected.
+    #[arg(long)]
+    bless: bool,
+    /// Verify the exercise again every time one of its files changes, then move on
+    /// to the next exercise once it behaves as expected.
+    #[arg(
+        long,
+        conflicts_with_all = ["workspace", "bless", "report_json", "report_junit"]
+    )]
+    watch: bool,
+    /// Verify the exercises N times in a row and report the tests whose outcome or
+    /// output isn't the same in every run. Progress and reports are not recorded.
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u16).range(1..),
+        conflicts_with_all = ["watch", "bless", "report_json", "report_junit"]
+    )]
+    repeat: u16,
+    #[command(flatten)]
+    selection: TestSelection,
+    #[command(flatten)]
+    timeouts: Timeouts,
+    #[command(flatten)]
+    report: ReportArgs,
 }
 
-#[derive(Debug, serde::Deserialize)]
-#[serde(tag = "expected_outcome")]
-#[serde(rename_all = "snake_case")]
-enum ExpectedOutcome {
-    Success,
-    Failure { expected_output: String },
+/// Keep track of the exercises that have been completed, in `.ctr/progress.json`,
+/// and check `expectations.yml` files.
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Show which exercises have been completed, following the order of the book.
+    Status,
+    /// Show the first exercise that hasn't been completed yet.
+    Next,
+    /// Forget the recorded progress for an exercise.
+    Reset {
+        /// The path of the exercise, or the trailing part of it (e.g. `03_eq`).
+        exercise: String,
+    },
+    /// Check every `expectations.yml` in the workspace against the schema and for
+    /// common mistakes.
+    Lint {
+        /// Print the JSON Schema of `expectations.yml` instead.
+        #[arg(long)]
+        print_schema: bool,
+    },
 }
 
 fn main() {
-    if let Err(e) = entrypoint() {
+    let mut cli = Cli::parse();
+    cli.backend = cli.backend.resolve();
+    if let Err(e) = timeout::kill_process_trees_on_interrupt() {
+        eprintln!("{:?}", e);
+        std::process::exit(1);
+    }
+    if let Some(command) = cli.command {
+        if let Err(e) = run_command(command, cli.backend, cli.timeouts) {
+            eprintln!("{:?}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Err(e) = entrypoint(cli) {
         eprintln!("Failed to verify expectations.\n{:?}", e);
         std::process::exit(1);
     } else {
@@ -34,242 +111,143 @@ fn main() {
     }
 }
 
-fn entrypoint() -> Result<(), anyhow::Error> {
-    let expectations_filepath = PathBuf::from("expectations.yml");
-    let raw_expectations = fs_err::read_to_string(expectations_filepath)
-        .expect("Failed to read `expectations.yml` file in the current directory");
-    let expectations: Expectations =
-        serde_yaml::from_str(&raw_expectations).expect("Failed to parse `expectations.yml` file");
-    let outcomes = run_tests().context("Failed to run tests")?;
-
-    // Exhaustiveness check: the list of tests in `expectations.yml` should match the list of tests
-    // that `cargo test` ran.
-    let discovered_tests: HashSet<_> = outcomes.keys().collect();
-    for test_name in discovered_tests {
-        if !expectations
-            .tests
-            .iter()
-            .any(|test| &test.name == test_name)
-        {
-            panic!(
-                "Test `{}` was run by `cargo test`, but it is not listed in `expectations.yml`.\n\
-                This is a bug in the workshop, please report it to the instructor!",
-                test_name
-            )
+fn entrypoint(cli: Cli) -> Result<(), anyhow::Error> {
+    let current_dir = std::env::current_dir()?;
+    let workspace_root = match workspace::find_workspace_root(&current_dir) {
+        Ok(root) => Some(root),
+        Err(e) if cli.workspace => return Err(e),
+        Err(_) => None,
+    };
+    if cli.watch {
+        return watch::watch(
+            workspace_root.as_deref(),
+            &current_dir,
+            cli.backend,
+            &cli.selection,
+            cli.timeouts,
+        );
+    }
+    if cli.repeat > 1 {
+        let exercises = if cli.workspace {
+            let root = workspace_root.as_deref().expect("We checked it above");
+            workspace::discover_exercises(root)?
+                .into_iter()
+                .map(|exercise| (workspace::display_name(root, &exercise), exercise))
+                .collect()
+        } else {
+            let name = match &workspace_root {
+                Some(root) => workspace::display_name(root, &current_dir),
+                None => current_dir.display().to_string(),
+            };
+            vec![(name, PathBuf::from("."))]
+        };
+        return stability::check_stability(
+            &exercises,
+            cli.backend,
+            &cli.selection,
+            cli.timeouts,
+            cli.repeat.into(),
+            cli.jobs,
+        );
+    }
+    let runs = if cli.workspace {
+        let root = workspace_root.as_deref().expect("We checked it above");
+        workspace::verify_workspace(root, cli.backend, &cli.selection, cli.timeouts, cli.jobs)?
+    } else {
+        let run = ExerciseRun {
+            name: match &workspace_root {
+                Some(root) => workspace::display_name(root, &current_dir),
+                None => current_dir.display().to_string(),
+            },
+            dir: PathBuf::from("."),
+            result: verify::verify_exercise(
+                Path::new("."),
+                cli.backend,
+                &cli.selection,
+                cli.timeouts,
+            ),
+        };
+        if let Ok(report) = &run.result {
+            print!("{}", report.render());
         }
+        vec![run]
+    };
+    cli.report.write(&runs)?;
+    // A partial run doesn't tell us whether the exercises are completed, and blessing
+    // rewrites the expectations the run was checked against.
+    if let Some(root) = workspace_root
+        .as_ref()
+        .filter(|_| !cli.selection.is_filtered() && !cli.bless)
+    {
+        progress::record_runs(root, &runs)?;
     }
 
-    let mut failed = false;
+    if cli.bless {
+        for run in &runs {
+            bless(run)?;
+        }
+        return Ok(());
+    }
 
-    for test in expectations.tests {
-        let outcome = outcomes.get(&test.name).with_context(|| {
-            format!(
-                "There is no entry in `cargo test` output for a test named `{}`",
-                &test.name
+    if cli.workspace {
+        let n_failed = runs.iter().filter(|run| !run.passed()).count();
+        if n_failed > 0 {
+            anyhow::bail!(
+                "{} out of {} exercises didn't behave as expected",
+                n_failed,
+                runs.len()
             )
-        })?;
-        let intro_msg = format!("🔘 Checking test `{}` against expectations", test.name);
-        println!("{}", intro_msg.bold());
-        match outcome {
-            TestOutcome::Ok { .. } => {
-                if let ExpectedOutcome::Failure { .. } = test.outcome {
-                    println!(
-                        "{}",
-                        format!("Test `{}` succeeded, but was expected to fail", test.name)
-                            .bold()
-                            .red()
-                    );
-                    failed = true;
-                }
-            }
-            TestOutcome::Failed {
-                clean_stdout,
-                raw_stdout,
-            } => match test.outcome {
-                ExpectedOutcome::Success => {
-                    println!(
-                        "{}\n{}:\n{}",
-                        format!("Test `{}` failed, but was expected to succeed.", test.name)
-                            .bold()
-                            .red(),
-                        "Raw test output:".bold(),
-                        textwrap::indent(raw_stdout, "    ")
-                    );
-                    failed = true;
-                }
-                ExpectedOutcome::Failure { expected_output } => {
-                    if clean_stdout != &expected_output {
-                        let failure_msg_1 = format!("❌ `{}`", test.name);
-                        let failure_msg_2 = "The test failed as expected, but the failure output doesn't match the expected output from `expectations.yml`.".to_string();
-                        println!("{}\n{}\n", failure_msg_1.red().bold(), failure_msg_2.red());
-                        println!("{}", StrComparison::new(&clean_stdout, &expected_output));
-                        println!(
-                            "{}\n{}",
-                            "Raw test output:".bold(),
-                            textwrap::indent(raw_stdout, "    ")
-                        );
-                        failed = true;
-                    }
-                }
-            },
-            TestOutcome::Timeout => {
-                println!("{}", format!("Test `{}` timed out", test.name).bold().red());
-                failed = true;
-            }
         }
+        return Ok(());
     }
-
-    if failed {
+    let run = runs
+        .into_iter()
+        .next()
+        .expect("We verified a single exercise");
+    if !run.result?.passed() {
         anyhow::bail!("One or more tests didn't behave as expected")
     }
     Ok(())
 }
 
-/// Execute `cargo test` in the current directory.
-///
-/// We use the unstable `--format` option to get the output in JSON format.
-/// The output is going to be a mix of JSON and non-JSON lines, so we need to
-/// filter out the non-JSON lines.
-/// For the JSON lines, we parse them and keep the ones with `type` set to `test`,
-/// indicating the outcome of a particular test.
-/// We then return a `test name -> test outcome` mapping.
-fn run_tests() -> Result<HashMap<String, TestOutcome>, anyhow::Error> {
-    static THREAD_PANIC: Lazy<regex::Regex> = Lazy::new(|| {
-        regex::Regex::new(r#"thread \'[a-zA-Z0-9\:\-\_]+\' panicked at [a-zA-Z0-9\-\\\_\/\.]+\:(?<row>\d+)\:(?<column>\d+)"#)
-            .expect("Failed to compile regex")
-    });
-    static MISSING_NIGHTLY: Lazy<regex::Regex> = Lazy::new(|| {
-        regex::Regex::new(r#"error: toolchain 'nightly-[a-zA-Z0-9\-]+' is not installed"#)
-            .expect("Failed to compile regex")
-    });
-    static GOOGLETEST_PANIC: Lazy<regex::Regex> = Lazy::new(|| {
-        regex::Regex::new(r#"\s*at [a-zA-Z0-9\-\\\_\/\.\:]+\:(?<row>\d+)\:(?<column>\d+)"#)
-            .expect("Failed to compile regex")
-    });
-
-    let mut command = std::process::Command::new("rustup");
-    command
-        .arg("run")
-        .arg("nightly")
-        .arg("cargo")
-        .arg("test")
-        .arg("--quiet")
-        .arg("--no-fail-fast")
-        .arg("--")
-        .arg("-Z")
-        .arg("unstable-options")
-        .arg("--format")
-        .arg("json");
-    let raw_output = command
-        .output()
-        .context("Failed to run `rustup run nightly cargo test`")?;
-    let stdout = String::from_utf8(raw_output.stdout).context("stdout contains invalid UTF-8")?;
-    let stderr = String::from_utf8(raw_output.stderr).context("stdout contains invalid UTF-8")?;
-    if MISSING_NIGHTLY.is_match(&stderr) {
-        anyhow::bail!(
-            "You need to install the nightly toolchain: `rustup toolchain install nightly`"
-        )
+fn run_command(
+    command: Command,
+    backend: Backend,
+    timeouts: Timeouts,
+) -> Result<(), anyhow::Error> {
+    if let Command::Lint { print_schema: true } = command {
+        print!("{}", lint::SCHEMA);
+        return Ok(());
     }
-    let mut test_outcomes = HashMap::new();
-    for line in stdout.lines() {
-        let Ok(libtest_msg) = serde_json::from_str::<serde_json::Value>(line) else {
-            continue;
-        };
-        if !is_test_event(&libtest_msg) {
-            continue;
-        }
-        let libtest_msg = serde_json::from_value::<LibtestMessage>(libtest_msg)
-            .context("Failed to parse libtest message")?;
-
-        let test_name = libtest_msg
-            .name
-            .split("::")
-            .last()
-            .expect("Failed to extract test name from libtest message")
-            .to_owned();
-        let test_outcome = match libtest_msg.event_data {
-            TestEventData::Started => continue,
-            TestEventData::Failed { stdout } => {
-                let stdout = stdout.unwrap_or_default();
-                let mut clean_stdout = Vec::new();
-                let mut found_non_empty_line = false;
-                for line in stdout.lines() {
-                    let trimmed = line.trim();
-                    if trimmed.is_empty() && !found_non_empty_line {
-                        continue;
-                    }
-                    if THREAD_PANIC.is_match(line)
-                        || GOOGLETEST_PANIC.is_match(trimmed)
-                        || trimmed.starts_with("note: run with `RUST_BACKTRACE=1`")
-                        || trimmed == "Test failed"
-                        || trimmed == "Error: See failure output above"
-                    {
-                        continue;
-                    }
-                    found_non_empty_line = true;
-                    clean_stdout.push(line);
-                }
-                // Remove trailing empty lines
-                while clean_stdout
-                    .last()
-                    .map(|l| l.trim().is_empty())
-                    .unwrap_or(false)
-                {
-                    clean_stdout.pop();
-                }
-
-                let clean_stdout = clean_stdout.join("\n");
-                TestOutcome::Failed {
-                    clean_stdout,
-                    raw_stdout: stdout,
-                }
-            }
-            TestEventData::Ok => TestOutcome::Ok,
-            TestEventData::Timeout => TestOutcome::Timeout,
-        };
-        test_outcomes.insert(test_name, test_outcome);
+    let current_dir = std::env::current_dir()?;
+    let workspace_root = workspace::find_workspace_root(&current_dir)?;
+    match command {
+        Command::Status => progress::print_status(&workspace_root, backend, timeouts),
+        Command::Next => progress::print_next(&workspace_root, backend, timeouts),
+        Command::Reset { exercise } => progress::reset(&workspace_root, &exercise),
+        Command::Lint { .. } => lint::lint_workspace(&workspace_root),
     }
-    Ok(test_outcomes)
-}
-
-#[derive(serde::Deserialize, Debug)]
-#[serde(tag = "event")]
-enum TestOutcome {
-    Failed {
-        clean_stdout: String,
-        raw_stdout: String,
-    },
-    Ok,
-    Timeout,
 }
 
-fn is_test_event(libtest_msg: &serde_json::Value) -> bool {
-    let Some(type_value) = libtest_msg.get("type") else {
-        return false;
-    };
-    let Some(type_value) = type_value.as_str() else {
-        return false;
+/// Rewrite the expectations of `run` and print what changed.
+fn bless(run: &ExerciseRun) -> Result<(), anyhow::Error> {
+    let Ok(report) = &run.result else {
+        eprintln!("⚠️ Skipping `{}`: it couldn't be verified", run.name);
+        return Ok(());
     };
-    type_value == "test"
-}
-
-/// Reference for the JSON output of `libtest`: https://github.com/rust-lang/rust/blob/master/library/test/src/formatters/json.rs
-///
-/// We only model events with `type` set to `test`. We ignore everything else.
-#[derive(serde::Deserialize, Debug)]
-#[serde(rename_all = "snake_case")]
-struct LibtestMessage {
-    name: String,
-    #[serde(flatten)]
-    event_data: TestEventData,
-}
-
-#[derive(serde::Deserialize, Debug)]
-#[serde(tag = "event")]
-#[serde(rename_all = "snake_case")]
-enum TestEventData {
-    Started,
-    Failed { stdout: Option<String> },
-    Ok,
-    Timeout,
+    if !run.dir.join("expectations.yml").is_file() {
+        return Ok(());
+    }
+    let blessed = bless::bless_exercise(&run.dir, report)
+        .with_context(|| format!("Failed to bless `{}`", run.name))?;
+    for test in &blessed.skipped {
+        eprintln!(
+            "⚠️ Skipping `{}` in `{}`: timed-out tests can't be blessed",
+            test, run.name
+        );
+    }
+    if let Some(diff) = blessed.diff() {
+        println!("📝 Updated `{}/expectations.yml`\n{}", run.name, diff);
+    }
+    Ok(())
 }
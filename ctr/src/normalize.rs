@@ -0,0 +1,209 @@
+//! Per-exercise normalisation of test output, configured in `expectations.yml`.
+//!
+//! Normalisers run, in the order they are declared, on the cleaned up output of failed
+//! tests and on panic messages, before they are checked against the expectations.
+//! They are meant for output that changes from run to run (temporary files, ports,
+//! timestamps, etc.):
+//!
+//! ```yaml
+//! normalize:
+//!   - strip_ansi
+//!   - redact_temp_dir
+//!   - replace:
+//!       pattern: "took \\d+ms"
+//!       with: "took [..]ms"
+//! tests:
+//!   # ...
+//! ```
+
+use crate::expectations::OutputRegex;
+use once_cell::sync::Lazy;
+use std::path::Path;
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(from = "NormalizerSpec")]
+pub enum Normalizer {
+    /// Remove ANSI escape sequences (colours, bold, etc.).
+    StripAnsi,
+    /// Replace the absolute path of the exercise directory with `[EXERCISE_DIR]`.
+    RedactPaths,
+    /// Replace paths inside the system temporary directory with `[TEMP_PATH]`,
+    /// e.g. the ones created by `tempfile::NamedTempFile`.
+    RedactTempDir,
+    /// Replace ISO 8601 timestamps (e.g. `2024-05-01T12:30:00Z`) with `[TIMESTAMP]`.
+    RemoveTimestamps,
+    /// Replace memory addresses (e.g. `0x7ffd5e8c1a2c`) with `[ADDRESS]` and
+    /// the port of socket addresses (e.g. `127.0.0.1:50123`) with `[PORT]`.
+    RemoveAddresses,
+    /// Replace every match of `pattern` with `with`, which can refer to capture groups
+    /// (e.g. `$1` or `${name}`).
+    Replace { pattern: OutputRegex, with: String },
+}
+
+/// How normalisers are spelled in `expectations.yml`.
+///
+/// `serde_yaml` expects `!tags` for enum variants with fields, so we accept
+/// `replace: { ... }` maps via an untagged enum instead.
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum NormalizerSpec {
+    Builtin(BuiltinNormalizer),
+    Replace { replace: Replacement },
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum BuiltinNormalizer {
+    StripAnsi,
+    RedactPaths,
+    RedactTempDir,
+    RemoveTimestamps,
+    RemoveAddresses,
+}
+
+#[derive(serde::Deserialize)]
+struct Replacement {
+    pattern: OutputRegex,
+    with: String,
+}
+
+impl From<NormalizerSpec> for Normalizer {
+    fn from(spec: NormalizerSpec) -> Self {
+        match spec {
+            NormalizerSpec::Builtin(BuiltinNormalizer::StripAnsi) => Normalizer::StripAnsi,
+            NormalizerSpec::Builtin(BuiltinNormalizer::RedactPaths) => Normalizer::RedactPaths,
+            NormalizerSpec::Builtin(BuiltinNormalizer::RedactTempDir) => Normalizer::RedactTempDir,
+            NormalizerSpec::Builtin(BuiltinNormalizer::RemoveTimestamps) => {
+                Normalizer::RemoveTimestamps
+            }
+            NormalizerSpec::Builtin(BuiltinNormalizer::RemoveAddresses) => {
+                Normalizer::RemoveAddresses
+            }
+            NormalizerSpec::Replace {
+                replace: Replacement { pattern, with },
+            } => Normalizer::Replace { pattern, with },
+        }
+    }
+}
+
+/// The normalisers of an exercise, ready to be applied.
+pub struct Pipeline<'a> {
+    normalizers: &'a [Normalizer],
+    /// The exercise directory, both as given and canonicalized.
+    exercise_dirs: Vec<String>,
+    /// The temporary directory, both as given and canonicalized (e.g. `/private/var/...` on macOS).
+    temp_dirs: Vec<String>,
+}
+
+impl<'a> Pipeline<'a> {
+    pub fn new(normalizers: &'a [Normalizer], exercise_dir: &Path) -> Self {
+        let spellings = |path: &Path| {
+            let mut spellings: Vec<String> = [path.to_owned()]
+                .into_iter()
+                .chain(fs_err::canonicalize(path).ok())
+                .filter(|path| path.is_absolute())
+                .filter_map(|path| path.to_str().map(|s| s.trim_end_matches('/').to_owned()))
+                .collect();
+            // Longest first, so that a prefix doesn't shadow a longer spelling.
+            spellings.sort_by_key(|s| std::cmp::Reverse(s.len()));
+            spellings.dedup();
+            spellings
+        };
+        Self {
+            normalizers,
+            exercise_dirs: spellings(exercise_dir),
+            temp_dirs: spellings(&std::env::temp_dir()),
+        }
+    }
+
+    pub fn apply(&self, output: &str) -> String {
+        self.normalizers
+            .iter()
+            .fold(output.to_owned(), |output, normalizer| {
+                self.apply_one(normalizer, output)
+            })
+    }
+
+    fn apply_one(&self, normalizer: &Normalizer, output: String) -> String {
+        static TIMESTAMP: Lazy<regex::Regex> = Lazy::new(|| {
+            regex::Regex::new(
+                r"\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}:\d{2}(\.\d+)?(Z|[+-]\d{2}:?\d{2})?",
+            )
+            .expect("Failed to compile regex")
+        });
+        static MEMORY_ADDRESS: Lazy<regex::Regex> = Lazy::new(|| {
+            regex::Regex::new(r"\b0x[0-9a-fA-F]{4,}\b").expect("Failed to compile regex")
+        });
+        static SOCKET_ADDRESS: Lazy<regex::Regex> = Lazy::new(|| {
+            regex::Regex::new(r"(\b\d{1,3}(?:\.\d{1,3}){3}|\blocalhost|\[[0-9a-fA-F:]+\]):\d+\b")
+                .expect("Failed to compile regex")
+        });
+
+        match normalizer {
+            Normalizer::StripAnsi => strip_ansi(&output),
+            Normalizer::RedactPaths => self.exercise_dirs.iter().fold(output, |output, dir| {
+                output.replace(dir.as_str(), "[EXERCISE_DIR]")
+            }),
+            Normalizer::RedactTempDir => self.temp_dirs.iter().fold(output, |output, dir| {
+                let pattern = format!(r#"{}/[^\s'"`]*"#, regex::escape(dir));
+                let regex = regex::Regex::new(&pattern).expect("Escaped patterns are valid");
+                regex.replace_all(&output, "[TEMP_PATH]").into_owned()
+            }),
+            Normalizer::RemoveTimestamps => {
+                TIMESTAMP.replace_all(&output, "[TIMESTAMP]").into_owned()
+            }
+            Normalizer::RemoveAddresses => {
+                let output = MEMORY_ADDRESS.replace_all(&output, "[ADDRESS]");
+                SOCKET_ADDRESS
+                    .replace_all(&output, "$1:[PORT]")
+                    .into_owned()
+            }
+            Normalizer::Replace { pattern, with } => pattern
+                .as_regex()
+                .replace_all(&output, with.as_str())
+                .into_owned(),
+        }
+    }
+}
+
+/// Remove ANSI escape sequences, e.g. the colours of `pretty_assertions` diffs.
+pub fn strip_ansi(s: &str) -> String {
+    static ANSI_ESCAPE: Lazy<regex::Regex> =
+        Lazy::new(|| regex::Regex::new(r"\x1b\[[0-9;]*[a-zA-Z]").expect("Failed to compile regex"));
+    ANSI_ESCAPE.replace_all(s, "").into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Normalizer, Pipeline};
+    use std::path::Path;
+
+    #[test]
+    fn normalizers_are_parsed_from_yaml() {
+        let raw = r#"
+- strip_ansi
+- remove_addresses
+- replace:
+    pattern: "took \\d+ms"
+    with: "took [..]ms"
+"#;
+        let normalizers: Vec<Normalizer> = serde_yaml::from_str(raw).unwrap();
+        let pipeline = Pipeline::new(&normalizers, Path::new("."));
+        assert_eq!(
+            pipeline.apply("\x1b[31mGET 127.0.0.1:50123\x1b[0m at 0x7ffd5e8c1a2c took 12ms"),
+            "GET 127.0.0.1:[PORT] at [ADDRESS] took [..]ms"
+        );
+    }
+
+    #[test]
+    fn temp_paths_are_redacted() {
+        let normalizers = [Normalizer::RedactTempDir];
+        let pipeline = Pipeline::new(&normalizers, Path::new("."));
+        let temp_file = std::env::temp_dir().join(".tmpAbC123");
+        let output = format!("Failed to open `{}`: not found", temp_file.display());
+        assert_eq!(
+            pipeline.apply(&output),
+            "Failed to open `[TEMP_PATH]`: not found"
+        );
+    }
+}
@@ -0,0 +1,302 @@
+//! Keep track of the exercises that have been completed.
+//!
+//! Every verification is recorded in `.ctr/progress.json`, at the root of the workspace,
+//! together with a hash of the sources of the exercise at that point in time.
+//! An exercise is completed if its last verification succeeded and its sources
+//! haven't changed since then.
+
+use crate::runner::{Backend, TestSelection};
+use crate::timeout::Timeouts;
+use crate::verify::{verify_exercise, ExerciseRun};
+use crate::workspace::{display_name, exercise_order, exercise_sources, find_workspace_root};
+use anyhow::Context;
+use owo_colors::OwoColorize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const PROGRESS_FILE: &str = ".ctr/progress.json";
+
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct Progress {
+    /// Keyed by the path of the exercise, relative to the workspace root.
+    #[serde(default)]
+    exercises: BTreeMap<String, ExerciseProgress>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct ExerciseProgress {
+    /// The SHA-256 hash of the exercise sources, when it was last verified.
+    sources_hash: String,
+    passed: bool,
+    /// When the exercise was last verified, in seconds since the Unix epoch.
+    verified_at: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Completed,
+    Failing,
+    /// The sources changed since the exercise was last verified.
+    Modified,
+    NotStarted,
+}
+
+impl Progress {
+    pub fn load(workspace_root: &Path) -> Result<Self, anyhow::Error> {
+        let path = workspace_root.join(PROGRESS_FILE);
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        let raw_progress = fs_err::read_to_string(&path)?;
+        serde_json::from_str(&raw_progress)
+            .with_context(|| format!("Failed to parse `{}`", PROGRESS_FILE))
+    }
+
+    pub fn save(&self, workspace_root: &Path) -> Result<(), anyhow::Error> {
+        let path = workspace_root.join(PROGRESS_FILE);
+        if let Some(parent) = path.parent() {
+            fs_err::create_dir_all(parent)?;
+        }
+        let raw_progress = serde_json::to_string_pretty(self)?;
+        fs_err::write(&path, raw_progress)
+            .with_context(|| format!("Failed to write `{}`", PROGRESS_FILE))
+    }
+
+    /// Record the outcome of verifying an exercise.
+    pub fn record(&mut self, run: &ExerciseRun) -> Result<(), anyhow::Error> {
+        let verified_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        self.exercises.insert(
+            run.name.clone(),
+            ExerciseProgress {
+                sources_hash: sources_hash(&run.dir)?,
+                passed: run.passed(),
+                verified_at,
+            },
+        );
+        Ok(())
+    }
+
+    pub fn status(&self, name: &str, exercise_dir: &Path) -> Result<Status, anyhow::Error> {
+        let Some(progress) = self.exercises.get(name) else {
+            return Ok(Status::NotStarted);
+        };
+        let status = if progress.sources_hash != sources_hash(exercise_dir)? {
+            Status::Modified
+        } else if progress.passed {
+            Status::Completed
+        } else {
+            Status::Failing
+        };
+        Ok(status)
+    }
+}
+
+/// Record the outcome of verifying some exercises in the progress file.
+pub fn record_runs(workspace_root: &Path, runs: &[ExerciseRun]) -> Result<(), anyhow::Error> {
+    let mut progress = Progress::load(workspace_root)?;
+    for run in runs {
+        progress.record(run)?;
+    }
+    progress.save(workspace_root)
+}
+
+/// Hash the files that can affect the outcome of the tests of an exercise.
+fn sources_hash(exercise_dir: &Path) -> Result<String, anyhow::Error> {
+    let mut files = Vec::new();
+    for source in exercise_sources(exercise_dir)? {
+        collect_files(&source, &mut files)?;
+    }
+    files.sort();
+    // Crates shared with other exercises are named after their path in the workspace,
+    // so that the hash doesn't change when the workspace is moved.
+    let workspace_root = find_workspace_root(&fs_err::canonicalize(exercise_dir)?).ok();
+    let mut hasher = Sha256::new();
+    for file in files {
+        let relative = file
+            .strip_prefix(exercise_dir)
+            .ok()
+            .or_else(|| file.strip_prefix(workspace_root.as_deref()?).ok())
+            .unwrap_or(&file);
+        hasher.update(relative.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update(fs_err::read(&file)?);
+        hasher.update([0]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> Result<(), anyhow::Error> {
+    if path.is_file() {
+        files.push(path.to_owned());
+    } else if path.is_dir() {
+        for entry in fs_err::read_dir(path)? {
+            collect_files(&entry?.path(), files)?;
+        }
+    }
+    Ok(())
+}
+
+/// The status of every exercise, in the order of the book.
+///
+/// Completed exercises whose sources changed are verified again first.
+fn refresh(
+    workspace_root: &Path,
+    backend: Backend,
+    timeouts: Timeouts,
+) -> Result<Vec<(String, Status)>, anyhow::Error> {
+    let mut progress = Progress::load(workspace_root)?;
+    let mut statuses = Vec::new();
+    for exercise in exercise_order(workspace_root)? {
+        let name = display_name(workspace_root, &exercise);
+        let mut status = progress.status(&name, &exercise)?;
+        let was_completed = progress
+            .exercises
+            .get(&name)
+            .is_some_and(|progress| progress.passed);
+        if status == Status::Modified && was_completed {
+            println!(
+                "🔄 `{}` changed since it was completed, verifying it again",
+                name
+            );
+            let run = ExerciseRun {
+                result: verify_exercise(&exercise, backend, &TestSelection::default(), timeouts),
+                name: name.clone(),
+                dir: exercise,
+            };
+            progress.record(&run)?;
+            status = if run.passed() {
+                Status::Completed
+            } else {
+                Status::Failing
+            };
+        }
+        statuses.push((name, status));
+    }
+    progress.save(workspace_root)?;
+    Ok(statuses)
+}
+
+/// `ctr status`: print the status of every exercise.
+pub fn print_status(
+    workspace_root: &Path,
+    backend: Backend,
+    timeouts: Timeouts,
+) -> Result<(), anyhow::Error> {
+    let statuses = refresh(workspace_root, backend, timeouts)?;
+    for (name, status) in &statuses {
+        let line = match status {
+            Status::Completed => format!("✅ {}", name).green().to_string(),
+            Status::Failing => format!("❌ {}", name).red().to_string(),
+            Status::Modified => format!("✏️  {} (changed since the last verification)", name),
+            Status::NotStarted => format!("⬜ {}", name),
+        };
+        println!("{}", line);
+    }
+    let n_completed = statuses
+        .iter()
+        .filter(|(_, status)| *status == Status::Completed)
+        .count();
+    println!(
+        "\n{}",
+        format!("{}/{} exercises completed", n_completed, statuses.len()).bold()
+    );
+    Ok(())
+}
+
+/// `ctr next`: print the first exercise that hasn't been completed yet.
+pub fn print_next(
+    workspace_root: &Path,
+    backend: Backend,
+    timeouts: Timeouts,
+) -> Result<(), anyhow::Error> {
+    let statuses = refresh(workspace_root, backend, timeouts)?;
+    match statuses
+        .iter()
+        .find(|(_, status)| *status != Status::Completed)
+    {
+        Some((name, _)) => println!("👉 Next up: `{}`", name.bold()),
+        None => println!("🎉 You've completed every exercise!"),
+    }
+    Ok(())
+}
+
+/// `ctr reset <exercise>`: forget the recorded progress for an exercise.
+///
+/// The exercise can be identified by its path or by a trailing part of it
+/// (e.g. `03_eq` or `01_better_assertions/03_eq`).
+pub fn reset(workspace_root: &Path, exercise: &str) -> Result<(), anyhow::Error> {
+    let name = match fs_err::canonicalize(exercise) {
+        Ok(path) if path.is_dir() => display_name(workspace_root, &path),
+        _ => {
+            let candidates: Vec<_> = exercise_order(workspace_root)?
+                .iter()
+                .map(|path| display_name(workspace_root, path))
+                .filter(|name| {
+                    name == exercise
+                        || name
+                            .strip_suffix(exercise)
+                            .is_some_and(|prefix| prefix.ends_with('/'))
+                })
+                .collect();
+            match candidates.as_slice() {
+                [] => anyhow::bail!("There is no exercise named `{}`", exercise),
+                [name] => name.clone(),
+                _ => anyhow::bail!(
+                    "`{}` is ambiguous, it matches:\n{}",
+                    exercise,
+                    candidates
+                        .iter()
+                        .map(|name| format!("- `{}`", name))
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                ),
+            }
+        }
+    };
+    let mut progress = Progress::load(workspace_root)?;
+    if progress.exercises.remove(&name).is_none() {
+        println!("There is no recorded progress for `{}`", name);
+        return Ok(());
+    }
+    progress.save(workspace_root)?;
+    println!("🧹 Forgot the progress for `{}`", name);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sources_hash;
+
+    #[test]
+    fn nested_crates_are_part_of_the_sources() {
+        let exercise = tempfile::tempdir().unwrap();
+        let dir = exercise.path();
+        fs_err::create_dir_all(dir.join("src")).unwrap();
+        fs_err::create_dir_all(dir.join("macros/src")).unwrap();
+        fs_err::write(
+            dir.join("Cargo.toml"),
+            "[package]\nname = \"exercise\"\n\n[dev-dependencies]\nmacros = { path = \"macros\" }\n",
+        )
+        .unwrap();
+        fs_err::write(dir.join("src/lib.rs"), "").unwrap();
+        fs_err::write(
+            dir.join("macros/Cargo.toml"),
+            "[package]\nname = \"macros\"\n",
+        )
+        .unwrap();
+        fs_err::write(dir.join("macros/src/lib.rs"), "// TODO").unwrap();
+        let before = sources_hash(dir).unwrap();
+
+        fs_err::create_dir_all(dir.join("macros/target")).unwrap();
+        fs_err::write(dir.join("macros/target/build.log"), "").unwrap();
+        assert_eq!(sources_hash(dir).unwrap(), before);
+
+        fs_err::write(dir.join("macros/src/lib.rs"), "// Done").unwrap();
+        assert_ne!(sources_hash(dir).unwrap(), before);
+    }
+}
@@ -0,0 +1,730 @@
+//! Machine-readable reports, for CI dashboards and grading scripts.
+//!
+//! Two formats are supported: JSON and JUnit XML.
+//! Both contain the same information for each test: the expected and the actual
+//! outcome, the diff between expected and actual failure output, and the raw output.
+
+use crate::expectations::{ExpectedOutcome, ExpectedOutput};
+use crate::harness::{HarnessMismatch, HarnessReport};
+use crate::normalize::strip_ansi;
+use crate::runner::TestOutcome;
+use crate::steps::StepReport;
+use crate::verify::{
+    CompileMismatch, CompileReport, ExerciseReport, ExerciseRun, Mismatch, TestReport,
+};
+use anyhow::Context;
+use std::fmt::Write;
+use std::path::PathBuf;
+
+/// Where to write reports, if anywhere.
+#[derive(Debug, Default, clap::Args)]
+pub struct ReportArgs {
+    /// Write a JSON report of the verification results to the given file.
+    #[arg(long, value_name = "PATH")]
+    pub report_json: Option<PathBuf>,
+    /// Write a JUnit XML report of the verification results to the given file.
+    #[arg(long, value_name = "PATH")]
+    pub report_junit: Option<PathBuf>,
+}
+
+impl ReportArgs {
+    pub fn write(&self, runs: &[ExerciseRun]) -> Result<(), anyhow::Error> {
+        if let Some(path) = &self.report_json {
+            let report = serde_json::to_string_pretty(&json_report(runs))
+                .context("Failed to serialize the JSON report")?;
+            fs_err::write(path, report).context("Failed to write the JSON report")?;
+        }
+        if let Some(path) = &self.report_junit {
+            fs_err::write(path, junit_report(runs)).context("Failed to write the JUnit report")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(serde::Serialize)]
+struct JsonReport<'a> {
+    passed: bool,
+    exercises: Vec<JsonExercise<'a>>,
+}
+
+#[derive(serde::Serialize)]
+struct JsonExercise<'a> {
+    name: &'a str,
+    passed: bool,
+    /// Set if the exercise couldn't be verified at all.
+    error: Option<String>,
+    /// Set if `cargo test` was stopped because it exceeded a time limit.
+    killed: Option<&'a str>,
+    /// Set if the tests failed to compile, or if they were expected to.
+    compile: Option<JsonCompile<'a>>,
+    /// Set if `expectations.yml` describes a custom harness, rather than individual tests.
+    harness: Option<JsonHarness<'a>>,
+    tests: Vec<JsonTest<'a>>,
+    /// The `.wr.toml` verification steps that were executed, other than the tests.
+    steps: Vec<JsonStep<'a>>,
+    /// Tests run by `cargo test` that aren't listed in `expectations.yml`.
+    unlisted_tests: Vec<String>,
+    /// Entries of `expectations.yml` that don't refer to any of the tests that ran.
+    missing_tests: Vec<&'a str>,
+}
+
+#[derive(serde::Serialize)]
+struct JsonCompile<'a> {
+    passed: bool,
+    mismatch: Option<&'static str>,
+    expected_message: Option<&'a str>,
+    errors: Vec<JsonCompileError<'a>>,
+}
+
+#[derive(serde::Serialize)]
+struct JsonCompileError<'a> {
+    target: &'a str,
+    message: &'a str,
+    code: Option<&'a str>,
+    file: Option<&'a str>,
+    line: Option<usize>,
+    column: Option<usize>,
+    rendered: &'a str,
+}
+
+#[derive(serde::Serialize)]
+struct JsonHarness<'a> {
+    target: &'a str,
+    passed: bool,
+    mismatches: Vec<&'static str>,
+    killed: Option<&'a str>,
+    expected_exit_code: i32,
+    exit_code: Option<i32>,
+    expected_listed_tests: Option<&'a [String]>,
+    listed_tests: &'a [String],
+    stdout: &'a str,
+    stderr: &'a str,
+}
+
+#[derive(serde::Serialize)]
+struct JsonStep<'a> {
+    command: &'a str,
+    passed: bool,
+    exit_code: Option<i32>,
+    killed: Option<&'a str>,
+    output: &'a str,
+}
+
+#[derive(serde::Serialize)]
+struct JsonTest<'a> {
+    name: &'a str,
+    id: String,
+    passed: bool,
+    mismatch: Option<&'static str>,
+    expected_outcome: &'static str,
+    /// `exact`, `contains` or `regex`, for failures.
+    expected_output_kind: Option<&'static str>,
+    expected_output: Option<&'a str>,
+    actual_outcome: &'static str,
+    actual_output: Option<&'a str>,
+    actual_panic_message: Option<&'a str>,
+    diff: Option<String>,
+    raw_stdout: String,
+}
+
+fn json_report(runs: &[ExerciseRun]) -> JsonReport<'_> {
+    let exercises = runs
+        .iter()
+        .map(|run| {
+            let (error, report) = match &run.result {
+                Ok(report) => (None, Some(report)),
+                Err(e) => (Some(format!("{:?}", e)), None),
+            };
+            JsonExercise {
+                name: &run.name,
+                passed: run.passed(),
+                error,
+                killed: report.and_then(|report| report.killed.as_deref()),
+                compile: report.and_then(|report| report.compile.as_ref().map(json_compile)),
+                harness: report.and_then(|report| report.harness.as_ref().map(json_harness)),
+                tests: report
+                    .map(|report| report.tests.iter().map(json_test).collect())
+                    .unwrap_or_default(),
+                steps: report
+                    .map(|report| report.steps.iter().map(json_step).collect())
+                    .unwrap_or_default(),
+                unlisted_tests: report
+                    .map(|report| {
+                        report
+                            .unlisted
+                            .iter()
+                            .map(|test| test.test_id.to_string())
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                missing_tests: report
+                    .map(|report| {
+                        report
+                            .missing
+                            .iter()
+                            .map(|test| test.name.as_str())
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            }
+        })
+        .collect();
+    JsonReport {
+        passed: runs.iter().all(ExerciseRun::passed),
+        exercises,
+    }
+}
+
+fn json_test(test: &TestReport) -> JsonTest<'_> {
+    let (expected_outcome, expected_output_kind, expected_output) = match &test.expected {
+        ExpectedOutcome::Success => ("success", None, None),
+        ExpectedOutcome::Failure { output } => {
+            let kind = match output {
+                ExpectedOutput::Exact(_) => "exact",
+                ExpectedOutput::Contains(_) => "contains",
+                ExpectedOutput::Regex(_) => "regex",
+            };
+            ("failure", Some(kind), Some(output.as_str()))
+        }
+        ExpectedOutcome::Ignored => ("ignored", None, None),
+        ExpectedOutcome::Panic { message } => ("panic", None, Some(message.as_str())),
+    };
+    let (actual_outcome, actual_output, actual_panic_message) = match &test.actual {
+        TestOutcome::Ok { panic_message } => ("success", None, panic_message.as_deref()),
+        TestOutcome::Failed {
+            clean_stdout,
+            panic_message,
+            ..
+        } => (
+            "failure",
+            Some(clean_stdout.as_str()),
+            panic_message.as_deref(),
+        ),
+        TestOutcome::Ignored => ("ignored", None, None),
+        TestOutcome::Timeout => ("timeout", None, None),
+        TestOutcome::Interrupted => ("interrupted", None, None),
+    };
+    JsonTest {
+        name: &test.name,
+        id: test.test_id.to_string(),
+        passed: test.mismatch.is_none(),
+        mismatch: test.mismatch.map(mismatch_kind),
+        expected_outcome,
+        expected_output_kind,
+        expected_output,
+        actual_outcome,
+        actual_output,
+        actual_panic_message,
+        diff: test.diff().map(|diff| strip_ansi(&diff)),
+        raw_stdout: strip_ansi(test.raw_stdout()),
+    }
+}
+
+fn json_compile(compile: &CompileReport) -> JsonCompile<'_> {
+    JsonCompile {
+        passed: compile.mismatch.is_none(),
+        mismatch: compile.mismatch.map(compile_mismatch_kind),
+        expected_message: compile
+            .expected
+            .as_ref()
+            .map(|expected| expected.message.as_str()),
+        errors: compile
+            .errors
+            .iter()
+            .map(|error| JsonCompileError {
+                target: &error.target,
+                message: &error.message,
+                code: error.code.as_deref(),
+                file: error.location.as_ref().map(|l| l.file.as_str()),
+                line: error.location.as_ref().map(|l| l.line),
+                column: error.location.as_ref().map(|l| l.column),
+                rendered: &error.rendered,
+            })
+            .collect(),
+    }
+}
+
+fn json_harness(harness: &HarnessReport) -> JsonHarness<'_> {
+    JsonHarness {
+        target: &harness.expected.target,
+        passed: harness.passed(),
+        mismatches: harness
+            .mismatches
+            .iter()
+            .copied()
+            .map(harness_mismatch_kind)
+            .collect(),
+        killed: harness.killed.as_deref(),
+        expected_exit_code: harness.expected.expected_exit_code,
+        exit_code: harness.exit_code,
+        expected_listed_tests: harness.expected.listed_tests.as_deref(),
+        listed_tests: &harness.listed_tests,
+        stdout: &harness.stdout,
+        stderr: &harness.stderr,
+    }
+}
+
+fn harness_mismatch_kind(mismatch: HarnessMismatch) -> &'static str {
+    match mismatch {
+        HarnessMismatch::ExitCode => "unexpected_exit_code",
+        HarnessMismatch::Stdout => "unexpected_stdout",
+        HarnessMismatch::Stderr => "unexpected_stderr",
+        HarnessMismatch::ListedTests => "unexpected_listed_tests",
+    }
+}
+
+fn compile_mismatch_kind(mismatch: CompileMismatch) -> &'static str {
+    match mismatch {
+        CompileMismatch::UnexpectedCompileError => "unexpected_compile_error",
+        CompileMismatch::MissingCompileError => "missing_compile_error",
+        CompileMismatch::UnexpectedCompileErrorMessage => "unexpected_compile_error_message",
+    }
+}
+
+fn json_step(step: &StepReport) -> JsonStep<'_> {
+    JsonStep {
+        command: &step.command,
+        passed: step.passed(),
+        exit_code: step.exit_code,
+        killed: step.killed.as_deref(),
+        output: &step.output,
+    }
+}
+
+fn mismatch_kind(mismatch: Mismatch) -> &'static str {
+    match mismatch {
+        Mismatch::UnexpectedSuccess => "unexpected_success",
+        Mismatch::UnexpectedFailure => "unexpected_failure",
+        Mismatch::UnexpectedOutput => "unexpected_output",
+        Mismatch::UnexpectedIgnore => "unexpected_ignore",
+        Mismatch::MissingIgnore => "missing_ignore",
+        Mismatch::MissingPanic => "missing_panic",
+        Mismatch::UnexpectedPanicMessage => "unexpected_panic_message",
+        Mismatch::Timeout => "timeout",
+        Mismatch::Interrupted => "interrupted",
+    }
+}
+
+fn mismatch_message(test: &TestReport, mismatch: Mismatch) -> String {
+    match mismatch {
+        Mismatch::UnexpectedSuccess => "The test succeeded, but was expected to fail".into(),
+        Mismatch::UnexpectedFailure => "The test failed, but was expected to succeed".into(),
+        Mismatch::UnexpectedOutput => {
+            "The failure output doesn't match the expected output from `expectations.yml`".into()
+        }
+        Mismatch::UnexpectedIgnore => "The test was ignored, but was expected to run".into(),
+        Mismatch::MissingIgnore => "The test ran, but was expected to be ignored".into(),
+        Mismatch::MissingPanic => "The test was expected to panic, but it didn't".into(),
+        Mismatch::UnexpectedPanicMessage => {
+            "The panic message doesn't match the expected message from `expectations.yml`".into()
+        }
+        Mismatch::Timeout => format!("Test `{}` timed out", test.name),
+        Mismatch::Interrupted => format!(
+            "Test `{}` didn't complete before `cargo test` was stopped",
+            test.name
+        ),
+    }
+}
+
+/// Render a JUnit XML report: one `<testsuite>` per exercise, one `<testcase>` per test.
+///
+/// An exercise that couldn't be verified is reported as a suite with a single errored test case.
+fn junit_report(runs: &[ExerciseRun]) -> String {
+    let mut out = String::new();
+    let mut suites = String::new();
+    let (mut n_tests, mut n_failures, mut n_errors) = (0, 0, 0);
+    for run in runs {
+        match &run.result {
+            Ok(report) => {
+                let failures = report
+                    .tests
+                    .iter()
+                    .filter(|test| test.mismatch.is_some())
+                    .count()
+                    + report.steps.iter().filter(|step| !step.passed()).count()
+                    + report
+                        .compile
+                        .iter()
+                        .filter(|compile| compile.mismatch.is_some())
+                        .count()
+                    + report
+                        .harness
+                        .iter()
+                        .filter(|harness| !harness.passed())
+                        .count()
+                    + report.unlisted.len()
+                    + report.missing.len();
+                let n_cases = report.tests.len()
+                    + report.unlisted.len()
+                    + report.missing.len()
+                    + report.steps.len()
+                    + usize::from(report.compile.is_some())
+                    + usize::from(report.harness.is_some());
+                n_tests += n_cases;
+                n_failures += failures;
+                writeln!(
+                    suites,
+                    r#"  <testsuite name="{}" tests="{}" failures="{}" errors="0">"#,
+                    escape_xml(&run.name),
+                    n_cases,
+                    failures
+                )
+                .unwrap();
+                if let Some(compile) = &report.compile {
+                    junit_compile_case(&mut suites, &run.name, compile);
+                }
+                if let Some(harness) = &report.harness {
+                    junit_harness_case(&mut suites, &run.name, harness);
+                }
+                for test in &report.tests {
+                    junit_test_case(&mut suites, test);
+                }
+                junit_listing_cases(&mut suites, &run.name, report);
+                for step in &report.steps {
+                    junit_step_case(&mut suites, &run.name, step);
+                }
+            }
+            Err(e) => {
+                n_tests += 1;
+                n_errors += 1;
+                writeln!(
+                    suites,
+                    r#"  <testsuite name="{}" tests="1" failures="0" errors="1">"#,
+                    escape_xml(&run.name)
+                )
+                .unwrap();
+                writeln!(
+                    suites,
+                    r#"    <testcase name="expectations" classname="{}">"#,
+                    escape_xml(&run.name)
+                )
+                .unwrap();
+                writeln!(
+                    suites,
+                    r#"      <error message="{}">{}</error>"#,
+                    escape_xml(&e.to_string()),
+                    escape_xml(&format!("{:?}", e))
+                )
+                .unwrap();
+                writeln!(suites, "    </testcase>").unwrap();
+            }
+        }
+        writeln!(suites, "  </testsuite>").unwrap();
+    }
+
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+    writeln!(
+        out,
+        r#"<testsuites name="ctr" tests="{}" failures="{}" errors="{}">"#,
+        n_tests, n_failures, n_errors
+    )
+    .unwrap();
+    out.push_str(&suites);
+    writeln!(out, "</testsuites>").unwrap();
+    out
+}
+
+fn junit_test_case(out: &mut String, test: &TestReport) {
+    // JUnit consumers group test cases by class name: the module path is the closest thing we have.
+    let classname = test
+        .test_id
+        .to_string()
+        .rsplit_once("::")
+        .map(|(prefix, _)| prefix.to_owned())
+        .unwrap_or_default();
+    writeln!(
+        out,
+        r#"    <testcase name="{}" classname="{}">"#,
+        escape_xml(&test.name),
+        escape_xml(&classname)
+    )
+    .unwrap();
+    if let Some(mismatch) = test.mismatch {
+        let body = test
+            .diff()
+            .map(|diff| strip_ansi(&diff))
+            .unwrap_or_default();
+        writeln!(
+            out,
+            r#"      <failure message="{}" type="{}">{}</failure>"#,
+            escape_xml(&mismatch_message(test, mismatch)),
+            mismatch_kind(mismatch),
+            escape_xml(&body)
+        )
+        .unwrap();
+    } else if matches!(test.actual, TestOutcome::Ignored) {
+        writeln!(out, "      <skipped/>").unwrap();
+    }
+    if !test.raw_stdout().is_empty() {
+        writeln!(
+            out,
+            "      <system-out>{}</system-out>",
+            escape_xml(&strip_ansi(test.raw_stdout()))
+        )
+        .unwrap();
+    }
+    writeln!(out, "    </testcase>").unwrap();
+}
+
+/// A failed test case for each test that is missing from `expectations.yml`, or missing
+/// from the output of `cargo test`.
+fn junit_listing_cases(out: &mut String, exercise: &str, report: &ExerciseReport) {
+    let unlisted = report.unlisted.iter().map(|test| {
+        (
+            test.test_id.to_string(),
+            "The test is not listed in `expectations.yml`",
+            "unlisted",
+        )
+    });
+    let missing = report.missing.iter().map(|test| {
+        (
+            test.name.clone(),
+            "The test is listed in `expectations.yml`, but `cargo test` didn't run it",
+            "missing",
+        )
+    });
+    for (name, message, kind) in unlisted.chain(missing) {
+        writeln!(
+            out,
+            r#"    <testcase name="{}" classname="{}">"#,
+            escape_xml(&name),
+            escape_xml(exercise)
+        )
+        .unwrap();
+        writeln!(
+            out,
+            r#"      <failure message="{}" type="{}"/>"#,
+            escape_xml(message),
+            kind
+        )
+        .unwrap();
+        writeln!(out, "    </testcase>").unwrap();
+    }
+}
+
+fn junit_compile_case(out: &mut String, exercise: &str, compile: &CompileReport) {
+    writeln!(
+        out,
+        r#"    <testcase name="compilation" classname="{}">"#,
+        escape_xml(exercise)
+    )
+    .unwrap();
+    if let Some(mismatch) = compile.mismatch {
+        let message = match mismatch {
+            CompileMismatch::UnexpectedCompileError => "The tests failed to compile",
+            CompileMismatch::MissingCompileError => {
+                "The tests compiled, but they were expected not to"
+            }
+            CompileMismatch::UnexpectedCompileErrorMessage => {
+                "The tests failed to compile, but not with the expected error"
+            }
+        };
+        writeln!(
+            out,
+            r#"      <failure message="{}" type="{}"></failure>"#,
+            escape_xml(message),
+            compile_mismatch_kind(mismatch),
+        )
+        .unwrap();
+    }
+    let rendered: String = compile
+        .errors
+        .iter()
+        .map(|error| error.rendered.as_str())
+        .collect();
+    if !rendered.is_empty() {
+        writeln!(
+            out,
+            "      <system-out>{}</system-out>",
+            escape_xml(&rendered)
+        )
+        .unwrap();
+    }
+    writeln!(out, "    </testcase>").unwrap();
+}
+
+fn junit_harness_case(out: &mut String, exercise: &str, harness: &HarnessReport) {
+    writeln!(
+        out,
+        r#"    <testcase name="{}" classname="{}">"#,
+        escape_xml(&harness.expected.target),
+        escape_xml(exercise)
+    )
+    .unwrap();
+    if let Some(mismatch) = harness.mismatches.first() {
+        let message = harness
+            .mismatches
+            .iter()
+            .map(|mismatch| harness.mismatch_message(*mismatch))
+            .collect::<Vec<_>>()
+            .join("\n");
+        writeln!(
+            out,
+            r#"      <failure message="{}" type="{}"></failure>"#,
+            escape_xml(&message),
+            harness_mismatch_kind(*mismatch),
+        )
+        .unwrap();
+    }
+    if !harness.stdout.is_empty() {
+        writeln!(
+            out,
+            "      <system-out>{}</system-out>",
+            escape_xml(&harness.stdout)
+        )
+        .unwrap();
+    }
+    if !harness.stderr.is_empty() {
+        writeln!(
+            out,
+            "      <system-err>{}</system-err>",
+            escape_xml(&harness.stderr)
+        )
+        .unwrap();
+    }
+    writeln!(out, "    </testcase>").unwrap();
+}
+
+fn junit_step_case(out: &mut String, exercise: &str, step: &StepReport) {
+    writeln!(
+        out,
+        r#"    <testcase name="{}" classname="{}">"#,
+        escape_xml(&step.command),
+        escape_xml(exercise)
+    )
+    .unwrap();
+    if !step.passed() {
+        let (message, kind) = match &step.killed {
+            Some(reason) => (reason.clone(), "timeout"),
+            None => (format!("`{}` failed", step.command), "step_failed"),
+        };
+        writeln!(
+            out,
+            r#"      <failure message="{}" type="{}"></failure>"#,
+            escape_xml(&message),
+            kind
+        )
+        .unwrap();
+    }
+    if !step.output.is_empty() {
+        writeln!(
+            out,
+            "      <system-out>{}</system-out>",
+            escape_xml(&step.output)
+        )
+        .unwrap();
+    }
+    writeln!(out, "    </testcase>").unwrap();
+}
+
+fn escape_xml(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Control characters (other than whitespace) are not allowed in XML 1.0
+            c if c.is_control() && !matches!(c, '\n' | '\r' | '\t') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::TestId;
+    use crate::verify::ExerciseReport;
+
+    fn test_report(name: &str, actual: TestOutcome, mismatch: Option<Mismatch>) -> TestReport {
+        TestReport {
+            name: name.into(),
+            entry: 0,
+            test_id: TestId {
+                binary: None,
+                path: format!("tests::{}", name),
+            },
+            expected: ExpectedOutcome::Success,
+            actual,
+            mismatch,
+        }
+    }
+
+    #[test]
+    fn xml_special_and_control_characters_are_escaped() {
+        assert_eq!(
+            escape_xml("<a href=\"x\">Tom & 'Jerry'</a>\u{7}\n"),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; &apos;Jerry&apos;&lt;/a&gt;\n"
+        );
+    }
+
+    #[test]
+    fn ansi_escape_sequences_are_stripped() {
+        assert_eq!(
+            strip_ansi("\x1b[1m\x1b[31m<left\x1b[0m / \x1b[32mright>\x1b[39m"),
+            "<left / right>"
+        );
+    }
+
+    #[test]
+    fn junit_reports_failures_timeouts_and_skipped_tests() {
+        let failed = test_report(
+            "failed",
+            TestOutcome::Failed {
+                clean_stdout: "assertion failed".into(),
+                raw_stdout: "\x1b[31massertion failed\x1b[0m & more".into(),
+                panic_message: None,
+            },
+            Some(Mismatch::UnexpectedFailure),
+        );
+        let timed_out = test_report("slow", TestOutcome::Timeout, Some(Mismatch::Timeout));
+        let ignored = TestReport {
+            expected: ExpectedOutcome::Ignored,
+            ..test_report("ignored", TestOutcome::Ignored, None)
+        };
+        let runs = [ExerciseRun {
+            name: "01_intro".into(),
+            dir: PathBuf::from("exercises/01_intro"),
+            result: Ok(ExerciseReport {
+                tests: vec![failed, timed_out, ignored],
+                steps: Vec::new(),
+                killed: None,
+                compile: None,
+                harness: None,
+                unlisted: Vec::new(),
+                missing: Vec::new(),
+            }),
+        }];
+
+        let report = junit_report(&runs);
+        assert!(report.contains(r#"<testsuites name="ctr" tests="3" failures="2" errors="0">"#));
+        assert!(report.contains(r#"<testsuite name="01_intro" tests="3" failures="2" errors="0">"#));
+        assert!(report.contains(concat!(
+            r#"    <testcase name="failed" classname="tests">"#,
+            "\n",
+            r#"      <failure message="The test failed, but was expected to succeed" type="unexpected_failure">"#,
+        )));
+        assert!(report.contains("<system-out>assertion failed &amp; more</system-out>"));
+        assert!(report.contains(concat!(
+            r#"    <testcase name="slow" classname="tests">"#,
+            "\n",
+            r#"      <failure message="Test `slow` timed out" type="timeout">"#,
+        )));
+        assert!(report.contains(concat!(
+            r#"    <testcase name="ignored" classname="tests">"#,
+            "\n",
+            "      <skipped/>\n",
+            "    </testcase>",
+        )));
+
+        let json = serde_json::to_value(json_report(&runs)).unwrap();
+        assert_eq!(
+            json["exercises"][0]["tests"][0]["raw_stdout"],
+            "assertion failed & more"
+        );
+    }
+}
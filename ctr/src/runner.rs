@@ -0,0 +1,497 @@
+use crate::diagnostics::{parse_compile_errors, CompileError};
+use crate::libtest::{parse_human_output, parse_json_output, TestBinary, TestEventData};
+use crate::normalize::Pipeline;
+use crate::timeout::{kill_process_tree, TimeLimits, TrackedTree, Watchdog};
+use anyhow::Context;
+use once_cell::sync::Lazy;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::Read;
+use std::path::Path;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::Duration;
+
+/// How `ctr` collects test outcomes from `cargo test`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Backend {
+    /// Use `nightly` if the toolchain is installed, `stable` otherwise.
+    Auto,
+    /// Parse the JSON output of libtest. Requires the nightly toolchain.
+    Nightly,
+    /// Parse the human-readable output of libtest. Works on any toolchain.
+    Stable,
+}
+
+impl Backend {
+    /// Resolve `Backend::Auto` into a concrete backend.
+    ///
+    /// It runs `rustup` to look for the nightly toolchain: `main` resolves the backend once,
+    /// everything else gets the resolved one.
+    pub fn resolve(self) -> Backend {
+        match self {
+            Backend::Auto if is_nightly_installed() => Backend::Nightly,
+            Backend::Auto => Backend::Stable,
+            backend => backend,
+        }
+    }
+}
+
+fn is_nightly_installed() -> bool {
+    std::process::Command::new("rustup")
+        .args(["run", "nightly", "cargo", "--version"])
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+/// Which tests to run: these options are forwarded to `cargo test`.
+///
+/// When some tests are left out, `expectations.yml` is only checked against the ones that ran.
+#[derive(Debug, Clone, Default, clap::Args)]
+pub struct TestSelection {
+    /// Only run the tests of the given package(s).
+    #[arg(long, short, value_name = "SPEC")]
+    pub package: Vec<String>,
+    /// Space or comma separated list of features to activate.
+    #[arg(long, short = 'F')]
+    pub features: Vec<String>,
+    /// Only run the tests of the given integration test target(s).
+    #[arg(long = "test", value_name = "NAME")]
+    pub test_targets: Vec<String>,
+    /// Only run the tests whose name contains this string.
+    #[arg(value_name = "TESTNAME")]
+    pub filter: Option<String>,
+    /// Extra arguments for the test binaries, e.g. `-- --exact` or `-- --skip slow`.
+    #[arg(last = true, value_name = "ARGS")]
+    pub libtest_args: Vec<String>,
+}
+
+impl TestSelection {
+    /// Whether some of the tests listed in `expectations.yml` may not run.
+    ///
+    /// `--package` and `--features` change how the tests are built, not which ones run:
+    /// every expected test must still show up.
+    pub fn is_filtered(&self) -> bool {
+        !self.test_targets.is_empty() || self.filter.is_some() || !self.libtest_args.is_empty()
+    }
+
+    /// The arguments that affect how the tests are built.
+    pub fn build_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        for package in &self.package {
+            args.extend(["--package".to_owned(), package.clone()]);
+        }
+        for features in &self.features {
+            args.extend(["--features".to_owned(), features.clone()]);
+        }
+        args
+    }
+
+    /// The arguments for `cargo test` itself, before `--`.
+    pub fn cargo_args(&self) -> Vec<String> {
+        let mut args = self.build_args();
+        for test in &self.test_targets {
+            args.extend(["--test".to_owned(), test.clone()]);
+        }
+        args.extend(self.filter.clone());
+        args
+    }
+}
+
+/// The fully qualified identity of a test.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TestId {
+    /// The test binary that ran the test.
+    pub binary: Option<TestBinary>,
+    /// The path of the test inside its binary, as reported by libtest (e.g. `tests::snapshot`).
+    pub path: String,
+}
+
+impl TestId {
+    /// Check if `name`, as written in `expectations.yml`, refers to this test.
+    ///
+    /// `name` can be the full path of the test, optionally prefixed with the name of
+    /// its binary, or any trailing subset of its `::`-separated segments.
+    /// E.g. `snapshot`, `tests::snapshot` and `snapshot_storage::tests::snapshot` all
+    /// match the `tests::snapshot` test in the `snapshot_storage` binary.
+    pub fn matches(&self, name: &str) -> bool {
+        let qualified = self.to_string();
+        qualified == name
+            || qualified
+                .strip_suffix(name)
+                .is_some_and(|prefix| prefix.ends_with("::"))
+    }
+}
+
+impl fmt::Display for TestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.binary {
+            Some(binary) => write!(f, "{}::{}", binary.name, self.path),
+            None => write!(f, "{}", self.path),
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Debug)]
+#[serde(tag = "event")]
+pub enum TestOutcome {
+    Failed {
+        clean_stdout: String,
+        raw_stdout: String,
+        /// The message of the panic that caused the failure, if any.
+        panic_message: Option<String>,
+    },
+    Ok {
+        /// The message of the panic expected by a `#[should_panic]` test.
+        ///
+        /// Only a panic on the test's own thread counts: a test that passes despite a panic
+        /// in a thread it spawned isn't a `#[should_panic]` test.
+        panic_message: Option<String>,
+    },
+    Ignored,
+    Timeout,
+    /// The test didn't complete because `cargo test` was stopped, e.g. because
+    /// another test exceeded its time limit.
+    Interrupted,
+}
+
+impl TestOutcome {
+    /// Apply the exercise's normalisers to the cleaned up output and to the panic message.
+    pub fn normalize(&mut self, pipeline: &Pipeline) {
+        match self {
+            TestOutcome::Failed {
+                clean_stdout,
+                panic_message,
+                ..
+            } => {
+                *clean_stdout = pipeline.apply(clean_stdout);
+                *panic_message = panic_message.as_deref().map(|m| pipeline.apply(m));
+            }
+            TestOutcome::Ok { panic_message } => {
+                *panic_message = panic_message.as_deref().map(|m| pipeline.apply(m));
+            }
+            TestOutcome::Ignored | TestOutcome::Timeout | TestOutcome::Interrupted => {}
+        }
+    }
+}
+
+/// The outcome of a `cargo test` invocation.
+pub struct TestRun {
+    pub outcomes: HashMap<TestId, TestOutcome>,
+    /// Why `cargo test` was killed before completing, if it was.
+    pub killed: Option<String>,
+    /// The errors that prevented the tests from compiling.
+    pub compile_errors: Vec<CompileError>,
+    /// `None` if `cargo test` was terminated by a signal.
+    pub exit_code: Option<i32>,
+    /// Everything `cargo test` printed, `stdout` and `stderr` interleaved.
+    pub output: String,
+}
+
+static THREAD_PANIC: Lazy<regex::Regex> = Lazy::new(|| {
+    regex::Regex::new(r#"thread \'[a-zA-Z0-9\:\-\_]+\'( \(\d+\))? panicked at [a-zA-Z0-9\-\\\_\/\.]+\:(?<row>\d+)\:(?<column>\d+)"#)
+        .expect("Failed to compile regex")
+});
+
+/// Execute `cargo test` in the given directory.
+///
+/// With the `nightly` backend, we use the unstable `--format json` option to get
+/// the output in a machine-readable format.
+/// With the `stable` backend, we ask for the `pretty` format and parse it.
+/// In both cases, we ask for the output of successful tests too (`--show-output`),
+/// in order to capture the panic message of `#[should_panic]` tests.
+///
+/// `stdout` and `stderr` are merged into a single stream, in order to interleave
+/// cargo's announcements of each test binary with the output of the binary itself.
+/// We then return a `test id -> test outcome` mapping.
+///
+/// Compiler diagnostics are requested in JSON (`--message-format json`), so that we can
+/// tell which errors, if any, prevented the tests from compiling.
+///
+/// The tests can be narrowed down with a [`TestSelection`]. `backend` must have been
+/// resolved already: `Backend::Auto` is treated as `Backend::Stable`.
+///
+/// The output is inspected as it comes in: if a time limit is exceeded, we kill
+/// `cargo test`, together with the test binaries it spawned, and report the tests
+/// that were still running as timed out.
+pub fn run_tests(
+    dir: &Path,
+    env: &BTreeMap<String, String>,
+    backend: Backend,
+    selection: &TestSelection,
+    limits: &TimeLimits,
+) -> Result<TestRun, anyhow::Error> {
+    static MISSING_NIGHTLY: Lazy<regex::Regex> = Lazy::new(|| {
+        regex::Regex::new(r#"error: toolchain 'nightly-[a-zA-Z0-9\-]+' is not installed"#)
+            .expect("Failed to compile regex")
+    });
+
+    let mut command = match backend {
+        Backend::Nightly => {
+            let mut command = std::process::Command::new("rustup");
+            command.args(["run", "nightly", "cargo"]);
+            command
+        }
+        _ => std::process::Command::new("cargo"),
+    };
+    command
+        .current_dir(dir)
+        .envs(env)
+        .arg("test")
+        .arg("--no-fail-fast")
+        .args(["--message-format", "json"])
+        .args(selection.cargo_args())
+        .arg("--");
+    match backend {
+        Backend::Nightly => command.args(["-Z", "unstable-options", "--format", "json"]),
+        _ => command.args(["--format", "pretty"]),
+    };
+    command.arg("--show-output").args(&selection.libtest_args);
+    let (reader, writer) = std::io::pipe().context("Failed to create a pipe")?;
+    command
+        .stdout(writer.try_clone().context("Failed to clone the pipe")?)
+        .stderr(writer);
+    // Put `cargo test` and its descendants in their own process group, so that
+    // we can kill all of them if they take too long.
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    let mut child = command.spawn().with_context(|| match backend {
+        Backend::Nightly => "Failed to run `rustup run nightly cargo test`",
+        _ => "Failed to run `cargo test`",
+    })?;
+    let _tracked = TrackedTree::new(&child);
+    // Drop our handles to the write end of the pipe, otherwise reading would never end.
+    drop(command);
+    let (chunks_tx, chunks_rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut reader = reader;
+        let mut buffer = [0; 8192];
+        loop {
+            match reader.read(&mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(n) if chunks_tx.send(buffer[..n].to_vec()).is_err() => break,
+                Ok(_) => {}
+            }
+        }
+    });
+    let mut watchdog = Watchdog::new(limits, backend == Backend::Nightly);
+    let mut raw_output = Vec::new();
+    let mut unterminated_line = Vec::new();
+    let mut killed = None;
+    loop {
+        match chunks_rx.recv_timeout(Duration::from_millis(100)) {
+            Ok(chunk) => {
+                raw_output.extend_from_slice(&chunk);
+                unterminated_line.extend(chunk);
+                while let Some(end) = unterminated_line.iter().position(|b| *b == b'\n') {
+                    let line: Vec<u8> = unterminated_line.drain(..=end).collect();
+                    watchdog.observe(String::from_utf8_lossy(&line).trim_end());
+                }
+            }
+            // libtest doesn't terminate the line it prints when a test starts (`test <name> ... `)
+            // until the test completes, so we look at it while we wait.
+            Err(RecvTimeoutError::Timeout) if !unterminated_line.is_empty() => {
+                watchdog.observe(String::from_utf8_lossy(&unterminated_line).trim_end());
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            // The pipe is closed once every process writing to it is gone.
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        if killed.is_none() {
+            if let Some(reason) = watchdog.check() {
+                kill_process_tree(&mut child)?;
+                killed = Some(reason);
+            }
+        }
+    }
+    let status = child.wait().context("Failed to wait for `cargo test`")?;
+    let output = String::from_utf8_lossy(&raw_output).into_owned();
+    if MISSING_NIGHTLY.is_match(&output) {
+        anyhow::bail!(
+            "You need to install the nightly toolchain: `rustup toolchain install nightly`.\n\
+            Alternatively, use `--backend stable` to run on the stable toolchain."
+        )
+    }
+    let messages = match backend {
+        Backend::Nightly => parse_json_output(&output)?,
+        _ => parse_human_output(&output),
+    };
+
+    let mut test_outcomes = HashMap::new();
+    for libtest_msg in messages {
+        let test_id = TestId {
+            binary: libtest_msg.binary,
+            path: libtest_msg.name,
+        };
+        let test_outcome = match libtest_msg.event_data {
+            TestEventData::Started => {
+                // If the test completed, this is overwritten by its outcome later on.
+                if killed.is_some() {
+                    test_outcomes.insert(test_id, TestOutcome::Timeout);
+                }
+                continue;
+            }
+            TestEventData::Failed { stdout } => {
+                let stdout = stdout.unwrap_or_default();
+                TestOutcome::Failed {
+                    clean_stdout: clean_stdout(&stdout),
+                    panic_message: panic_message(&stdout),
+                    raw_stdout: stdout,
+                }
+            }
+            TestEventData::Ok { stdout } => TestOutcome::Ok {
+                panic_message: stdout
+                    .as_deref()
+                    .and_then(|stdout| should_panic_message(stdout, &test_id.path)),
+            },
+            TestEventData::Ignored => TestOutcome::Ignored,
+            TestEventData::Timeout => TestOutcome::Timeout,
+        };
+        test_outcomes.insert(test_id, test_outcome);
+    }
+    Ok(TestRun {
+        outcomes: test_outcomes,
+        killed,
+        compile_errors: parse_compile_errors(&output),
+        exit_code: status.code(),
+        output,
+    })
+}
+
+/// Strip the noise that libtest and the panic handler add to the output of a failed test.
+fn clean_stdout(stdout: &str) -> String {
+    static GOOGLETEST_PANIC: Lazy<regex::Regex> = Lazy::new(|| {
+        regex::Regex::new(r#"\s*at [a-zA-Z0-9\-\\\_\/\.\:]+\:(?<row>\d+)\:(?<column>\d+)"#)
+            .expect("Failed to compile regex")
+    });
+
+    let mut clean_stdout = Vec::new();
+    let mut found_non_empty_line = false;
+    for line in stdout.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() && !found_non_empty_line {
+            continue;
+        }
+        if THREAD_PANIC.is_match(line)
+            || GOOGLETEST_PANIC.is_match(trimmed)
+            || trimmed.starts_with("note: run with `RUST_BACKTRACE=1`")
+            || trimmed == "Test failed"
+            || trimmed == "Error: See failure output above"
+        {
+            continue;
+        }
+        found_non_empty_line = true;
+        clean_stdout.push(line);
+    }
+    // Remove trailing empty lines
+    while clean_stdout
+        .last()
+        .map(|l| l.trim().is_empty())
+        .unwrap_or(false)
+    {
+        clean_stdout.pop();
+    }
+
+    clean_stdout.join("\n")
+}
+
+/// Extract the message of the first panic in the output of a test.
+///
+/// The panic handler prints `thread '<name>' panicked at <location>:` followed by the
+/// message, which can span multiple lines. The message ends where the backtrace,
+/// or the hint on how to enable it, begins.
+fn panic_message(stdout: &str) -> Option<String> {
+    message_after(stdout, |line| THREAD_PANIC.is_match(line))
+}
+
+/// Extract the message of the panic that made a `#[should_panic]` test pass.
+///
+/// libtest runs each test on a thread named after it, so we only look at the panics of
+/// that thread.
+fn should_panic_message(stdout: &str, test_name: &str) -> Option<String> {
+    let thread = format!("thread '{}'", test_name);
+    message_after(stdout, |line| {
+        THREAD_PANIC.is_match(line) && line.trim_start().starts_with(&thread)
+    })
+}
+
+/// The message of the first panic announced by a line matching `is_panic`.
+fn message_after(stdout: &str, is_panic: impl Fn(&str) -> bool) -> Option<String> {
+    let mut lines = stdout.lines().skip_while(|line| !is_panic(line));
+    lines.next()?;
+    let message: Vec<_> = lines
+        .take_while(|line| {
+            let trimmed = line.trim();
+            !trimmed.starts_with("stack backtrace:")
+                && !trimmed.starts_with("note: run with `RUST_BACKTRACE")
+                && !trimmed.starts_with("note: Some details are omitted")
+                && !THREAD_PANIC.is_match(line)
+        })
+        .collect();
+    Some(message.join("\n").trim_matches('\n').to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{panic_message, should_panic_message, TestId, TestSelection};
+    use crate::libtest::{TestBinary, TestKind};
+
+    #[test]
+    fn test_ids_match_trailing_path_segments() {
+        let id = TestId {
+            binary: Some(TestBinary {
+                name: "snapshot_storage".into(),
+                kind: TestKind::Unit,
+            }),
+            path: "tests::snapshot".into(),
+        };
+        assert!(id.matches("snapshot"));
+        assert!(id.matches("tests::snapshot"));
+        assert!(id.matches("snapshot_storage::tests::snapshot"));
+        assert!(!id.matches("shot"));
+        assert!(!id.matches("other::snapshot"));
+    }
+
+    #[test]
+    fn build_options_dont_filter_tests() {
+        let build_only = TestSelection {
+            package: vec!["hooks".into()],
+            features: vec!["tokio".into()],
+            ..Default::default()
+        };
+        assert!(!build_only.is_filtered());
+        let by_name = TestSelection {
+            filter: Some("snapshot".into()),
+            ..build_only
+        };
+        assert!(by_name.is_filtered());
+    }
+
+    #[test]
+    fn panic_messages_end_where_the_backtrace_begins() {
+        let stdout = "\nthread 'tests::sad' (22763) panicked at src/lib.rs:6:51:\n\
+            Failed to open config file\n\
+            stack backtrace:\n   0: __rustc::rust_begin_unwind\n";
+        assert_eq!(
+            panic_message(stdout).as_deref(),
+            Some("Failed to open config file")
+        );
+        assert_eq!(panic_message("Error: \"not a panic\""), None);
+    }
+
+    #[test]
+    fn only_panics_on_the_test_thread_make_a_should_panic_test_pass() {
+        let stdout = "thread '<unnamed>' panicked at src/lib.rs:3:9:\n\
+            Unrelated\n\
+            thread 'tests::boom' panicked at src/lib.rs:6:5:\n\
+            Boom\n\
+            note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace\n";
+        assert_eq!(
+            should_panic_message(stdout, "tests::boom").as_deref(),
+            Some("Boom")
+        );
+        assert_eq!(should_panic_message(stdout, "tests::other"), None);
+    }
+}
@@ -0,0 +1,404 @@
+//! Detect flaky tests by verifying the same exercises several times in a row.
+//!
+//! Some tests pass or fail depending on timing (e.g. mock servers or database pools).
+//! We record what each test did in every run, and flag as flaky any test whose outcome
+//! or output isn't the same across runs.
+
+use crate::runner::{Backend, TestOutcome, TestSelection};
+use crate::timeout::Timeouts;
+use crate::verify::{verify_exercise, ExerciseReport};
+use crate::workspace::in_parallel;
+use once_cell::sync::Lazy;
+use owo_colors::OwoColorize;
+use pretty_assertions::StrComparison;
+use std::fmt::Write;
+use std::path::PathBuf;
+
+/// What we observed for a test in a single run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Observation {
+    /// e.g. `success`, `failure` or `timeout`.
+    pub outcome: String,
+    /// The cleaned up output of a failure, or the message of a panic.
+    pub output: Option<String>,
+}
+
+/// How a test (or a verification step) behaved across all runs.
+#[derive(Debug)]
+pub struct TestStability {
+    /// How we refer to the test in the output, e.g. `` `happy_test` ``.
+    pub name: String,
+    /// Each distinct observation, with the number of runs in which it was made,
+    /// in the order they first occurred.
+    pub observations: Vec<(Observation, usize)>,
+    /// The number of runs in which the test behaved as expected.
+    pub n_as_expected: usize,
+}
+
+impl TestStability {
+    pub fn is_flaky(&self) -> bool {
+        self.observations.len() > 1
+    }
+}
+
+/// How the tests of an exercise behaved across `n_runs` runs.
+#[derive(Debug)]
+pub struct ExerciseStability {
+    pub name: String,
+    pub n_runs: usize,
+    pub tests: Vec<TestStability>,
+}
+
+impl ExerciseStability {
+    /// Aggregate the observations of each run, in the order the runs were made.
+    ///
+    /// A test that shows up in some runs but not in others (e.g. because the exercise
+    /// failed to compile once) is flaky too: it's recorded as `not run` when missing.
+    fn new(name: String, runs: &[Vec<(String, Observation, bool)>]) -> Self {
+        let mut tests: Vec<TestStability> = Vec::new();
+        for run in runs {
+            for (test_name, observation, as_expected) in run {
+                let i = match tests.iter().position(|test| &test.name == test_name) {
+                    Some(i) => i,
+                    None => {
+                        tests.push(TestStability {
+                            name: test_name.clone(),
+                            observations: Vec::new(),
+                            n_as_expected: 0,
+                        });
+                        tests.len() - 1
+                    }
+                };
+                let test = &mut tests[i];
+                match test.observations.iter_mut().find(|(o, _)| o == observation) {
+                    Some((_, count)) => *count += 1,
+                    None => test.observations.push((observation.clone(), 1)),
+                }
+                if *as_expected {
+                    test.n_as_expected += 1;
+                }
+            }
+        }
+        for test in &mut tests {
+            let n_observed: usize = test.observations.iter().map(|(_, count)| count).sum();
+            if n_observed < runs.len() {
+                let not_run = Observation {
+                    outcome: "not run".into(),
+                    output: None,
+                };
+                test.observations.push((not_run, runs.len() - n_observed));
+            }
+        }
+        Self {
+            name,
+            n_runs: runs.len(),
+            tests,
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for test in &self.tests {
+            if test.is_flaky() {
+                let msg = format!("⚠️ {} is flaky:", test.name);
+                writeln!(out, "{}", msg.bold().yellow()).unwrap();
+                render_observations(&mut out, test);
+                continue;
+            }
+            let (observation, _) = &test.observations[0];
+            let msg = format!(
+                "{}: {} in {}/{} runs",
+                test.name, observation.outcome, self.n_runs, self.n_runs
+            );
+            if test.n_as_expected == self.n_runs {
+                writeln!(out, "✅ {}", msg).unwrap();
+            } else {
+                let msg = format!("❌ {}, but that's not what was expected", msg);
+                writeln!(out, "{}", msg.red()).unwrap();
+            }
+        }
+        out
+    }
+}
+
+/// List each distinct observation, showing how its output differs from the first one.
+fn render_observations(out: &mut String, test: &TestStability) {
+    let (first, _) = &test.observations[0];
+    for (i, (observation, count)) in test.observations.iter().enumerate() {
+        writeln!(out, "    {}× {}", count, observation.outcome).unwrap();
+        let Some(output) = &observation.output else {
+            continue;
+        };
+        match &first.output {
+            Some(first_output) if i > 0 => {
+                if first_output != output {
+                    let diff = StrComparison::new(first_output, output).to_string();
+                    writeln!(out, "{}", textwrap::indent(&diff, "        ")).unwrap();
+                }
+            }
+            _ => writeln!(out, "{}", textwrap::indent(output, "        ")).unwrap(),
+        }
+    }
+}
+
+/// `in 0.42s`, as in cargo's `Finished` line or libtest's `test result` one.
+static ELAPSED_TIME: Lazy<regex::Regex> =
+    Lazy::new(|| regex::Regex::new(r"\bin \d+(\.\d+)?s\b").expect("Failed to compile regex"));
+
+/// What we observed for each test of an exercise in a single run, together with
+/// whether it behaved as expected.
+fn observe(result: &Result<ExerciseReport, anyhow::Error>) -> Vec<(String, Observation, bool)> {
+    let report = match result {
+        Ok(report) => report,
+        Err(e) => {
+            let observation = Observation {
+                outcome: "error".into(),
+                output: Some(format!("{:?}", e)),
+            };
+            return vec![("The verification".into(), observation, false)];
+        }
+    };
+    let mut observations = Vec::new();
+    if let Some(compile) = &report.compile {
+        let output = compile
+            .errors
+            .iter()
+            .map(|error| error.rendered.trim_end())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let outcome = if compile.errors.is_empty() {
+            "compiled"
+        } else {
+            "compile error"
+        };
+        let observation = Observation {
+            outcome: outcome.into(),
+            output: Some(output).filter(|output| !output.is_empty()),
+        };
+        observations.push((
+            "The compilation".into(),
+            observation,
+            compile.mismatch.is_none(),
+        ));
+    }
+    if let Some(harness) = &report.harness {
+        let outcome = match harness.exit_code {
+            Some(code) => format!("exit code {}", code),
+            None => "no exit code".into(),
+        };
+        let observation = Observation {
+            outcome,
+            output: Some(harness.stdout.clone()).filter(|stdout| !stdout.is_empty()),
+        };
+        observations.push((
+            format!("The `{}` harness", harness.expected.target),
+            observation,
+            harness.passed(),
+        ));
+    }
+    for test in &report.tests {
+        let (outcome, output) = match &test.actual {
+            TestOutcome::Failed { clean_stdout, .. } => ("failure", Some(clean_stdout.clone())),
+            TestOutcome::Ok { panic_message } => ("success", panic_message.clone()),
+            TestOutcome::Ignored => ("ignored", None),
+            TestOutcome::Timeout => ("timeout", None),
+            TestOutcome::Interrupted => ("interrupted", None),
+        };
+        let observation = Observation {
+            outcome: outcome.into(),
+            output,
+        };
+        observations.push((
+            format!("`{}`", test.name),
+            observation,
+            test.mismatch.is_none(),
+        ));
+    }
+    for step in &report.steps {
+        let outcome = match step.exit_code {
+            Some(code) => format!("exit code {}", code),
+            None => "no exit code".into(),
+        };
+        // Like a test's, the output of a step only matters when it fails: cargo prints
+        // how long it took, which is never the same twice.
+        let output = Some(&step.output)
+            .filter(|output| !step.passed() && !output.is_empty())
+            .map(|output| ELAPSED_TIME.replace_all(output, "in [..]s").into_owned());
+        let observation = Observation { outcome, output };
+        observations.push((format!("`{}`", step.command), observation, step.passed()));
+    }
+    observations
+}
+
+/// Verify each exercise `repeat` times in a row, using up to `jobs` exercises in parallel,
+/// and print how stable its tests are.
+///
+/// Fails if any test is flaky or consistently doesn't behave as expected.
+pub fn check_stability(
+    exercises: &[(String, PathBuf)],
+    backend: Backend,
+    selection: &TestSelection,
+    timeouts: Timeouts,
+    repeat: usize,
+    jobs: usize,
+) -> Result<(), anyhow::Error> {
+    let stabilities = in_parallel(exercises, jobs, |(name, dir)| {
+        let runs: Vec<_> = (0..repeat)
+            .map(|_| observe(&verify_exercise(dir, backend, selection, timeouts)))
+            .collect();
+        let stability = ExerciseStability::new(name.clone(), &runs);
+        if stability.tests.iter().any(TestStability::is_flaky) {
+            println!("⚠️ {}", name.yellow());
+        } else {
+            println!("🔁 {}", name);
+        }
+        stability
+    });
+
+    let mut n_flaky = 0;
+    let mut n_failing = 0;
+    let mut n_tests = 0;
+    for stability in &stabilities {
+        println!(
+            "\n{}\n{}",
+            format!("━━━ {} ({} runs) ━━━", stability.name, stability.n_runs).bold(),
+            stability.render()
+        );
+        n_tests += stability.tests.len();
+        n_flaky += stability.tests.iter().filter(|t| t.is_flaky()).count();
+        n_failing += stability
+            .tests
+            .iter()
+            .filter(|t| !t.is_flaky() && t.n_as_expected < stability.n_runs)
+            .count();
+    }
+    println!(
+        "{}\n",
+        format!(
+            "{} flaky and {} consistently failing, out of {} tests",
+            n_flaky, n_failing, n_tests
+        )
+        .bold()
+    );
+    if n_flaky > 0 || n_failing > 0 {
+        anyhow::bail!(
+            "One or more tests didn't behave the same way in every run, or consistently didn't behave as expected"
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::steps::StepReport;
+
+    fn observation(outcome: &str, output: Option<&str>) -> Observation {
+        Observation {
+            outcome: outcome.into(),
+            output: output.map(Into::into),
+        }
+    }
+
+    #[test]
+    fn tests_whose_outcome_or_output_changes_are_flaky() {
+        let stable = ("stable".to_string(), observation("success", None), true);
+        let runs = vec![
+            vec![
+                stable.clone(),
+                (
+                    "timing".into(),
+                    observation("failure", Some("took 3ms")),
+                    true,
+                ),
+                ("racy".into(), observation("success", None), false),
+            ],
+            vec![
+                stable.clone(),
+                (
+                    "timing".into(),
+                    observation("failure", Some("took 5ms")),
+                    false,
+                ),
+            ],
+            vec![
+                stable,
+                (
+                    "timing".into(),
+                    observation("failure", Some("took 3ms")),
+                    true,
+                ),
+                ("racy".into(), observation("failure", Some("boom")), true),
+            ],
+        ];
+        let stability = ExerciseStability::new("exercise".into(), &runs);
+        let [stable, timing, racy] = stability.tests.as_slice() else {
+            panic!("Expected three tests, got {:?}", stability.tests);
+        };
+        assert!(!stable.is_flaky());
+        assert_eq!(stable.n_as_expected, 3);
+
+        assert!(timing.is_flaky());
+        assert_eq!(timing.observations.len(), 2);
+        assert_eq!(timing.observations[0].1, 2);
+        assert_eq!(timing.n_as_expected, 2);
+
+        assert!(racy.is_flaky());
+        let outcomes: Vec<_> = racy
+            .observations
+            .iter()
+            .map(|(o, count)| (o.outcome.as_str(), *count))
+            .collect();
+        assert_eq!(outcomes, [("success", 1), ("failure", 1), ("not run", 1)]);
+    }
+
+    #[test]
+    fn steps_that_fail_differently_are_flaky() {
+        let run = |clippy_output: &str| {
+            let step = |command: &str, exit_code, output: &str| StepReport {
+                command: command.into(),
+                exit_code: Some(exit_code),
+                output: output.into(),
+                killed: None,
+            };
+            let report = ExerciseReport {
+                tests: vec![],
+                steps: vec![
+                    step("cargo build", 0, "Finished `dev` profile in 0.42s"),
+                    step("cargo clippy", 101, clippy_output),
+                ],
+                killed: None,
+                compile: None,
+                harness: None,
+                unlisted: Vec::new(),
+                missing: Vec::new(),
+            };
+            observe(&Ok(report))
+        };
+        let runs = [
+            run("error: unused variable `x`\nFinished in 0.42s"),
+            run("error: unused variable `x`\nFinished in 1.05s"),
+            run("error: unused variable `y`\nFinished in 0.42s"),
+        ];
+        let stability = ExerciseStability::new("exercise".into(), &runs);
+        let [build, clippy] = stability.tests.as_slice() else {
+            panic!("Expected two steps, got {:?}", stability.tests);
+        };
+        assert!(!build.is_flaky());
+        assert_eq!(build.n_as_expected, 3);
+
+        assert!(clippy.is_flaky());
+        let outputs: Vec<_> = clippy
+            .observations
+            .iter()
+            .map(|(o, count)| (o.output.as_deref(), *count))
+            .collect();
+        assert_eq!(
+            outputs,
+            [
+                (Some("error: unused variable `x`\nFinished in [..]s"), 2),
+                (Some("error: unused variable `y`\nFinished in [..]s"), 1),
+            ]
+        );
+    }
+}
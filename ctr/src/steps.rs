@@ -0,0 +1,268 @@
+//! Verification steps declared in `.wr.toml`, the configuration file of the workshop runner.
+//!
+//! ```toml
+//! [[verification]]
+//! command = "cargo"
+//! args = ["clippy", "--", "-D", "warnings"]
+//! env = { SQLX_OFFLINE = "true" }
+//! working_dir = "."
+//! ```
+//!
+//! Steps are executed in order and each of them must succeed.
+//! A plain `cargo test` step is special: if the exercise has an `expectations.yml` file,
+//! we run the tests ourselves and check the outcome of each of them against it.
+
+use crate::runner::TestSelection;
+use crate::timeout::{kill_process_tree, Timeouts, TrackedTree};
+use anyhow::Context;
+use owo_colors::OwoColorize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::Write;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, serde::Deserialize)]
+struct WrConfig {
+    #[serde(default)]
+    verification: Vec<VerificationStep>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct VerificationStep {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Extra environment variables for the command.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Where to run the command, relative to the exercise directory.
+    pub working_dir: Option<PathBuf>,
+}
+
+/// Load the verification steps of the exercise in `exercise_dir`, if it has a `.wr.toml` file.
+pub fn load_steps(exercise_dir: &Path) -> Result<Option<Vec<VerificationStep>>, anyhow::Error> {
+    let path = exercise_dir.join(".wr.toml");
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let raw_config = fs_err::read_to_string(&path).context("Failed to read `.wr.toml` file")?;
+    let config: WrConfig =
+        toml::from_str(&raw_config).context("Failed to parse `.wr.toml` file")?;
+    Ok(Some(config.verification))
+}
+
+impl VerificationStep {
+    pub fn cargo_test() -> Self {
+        Self {
+            command: "cargo".into(),
+            args: vec!["test".into()],
+            env: BTreeMap::new(),
+            working_dir: None,
+        }
+    }
+
+    /// Whether this step is a plain `cargo test`, i.e. the one `ctr` can check against
+    /// `expectations.yml`.
+    pub fn is_cargo_test(&self) -> bool {
+        self.command == "cargo" && self.args == ["test"]
+    }
+
+    /// Forward the test selection to a `cargo test` step.
+    pub fn with_selection(&self, selection: &TestSelection) -> Self {
+        let mut step = self.clone();
+        step.args.extend(selection.cargo_args());
+        if !selection.libtest_args.is_empty() {
+            step.args.push("--".into());
+            step.args.extend(selection.libtest_args.iter().cloned());
+        }
+        step
+    }
+
+    /// The directory the command runs in.
+    pub fn dir(&self, exercise_dir: &Path) -> PathBuf {
+        match &self.working_dir {
+            Some(working_dir) => exercise_dir.join(working_dir),
+            None => exercise_dir.to_owned(),
+        }
+    }
+
+    /// Run the step and capture its output.
+    ///
+    /// The step is killed if it runs for longer than `--run-timeout`.
+    pub fn run(
+        &self,
+        exercise_dir: &Path,
+        timeouts: Timeouts,
+    ) -> Result<StepReport, anyhow::Error> {
+        let mut command = Command::new(&self.command);
+        // Both streams go to the same pipe, so that the output reads as it would
+        // in a terminal.
+        let (mut reader, writer) = std::io::pipe().context("Failed to create a pipe")?;
+        command
+            .args(&self.args)
+            .envs(&self.env)
+            .current_dir(self.dir(exercise_dir))
+            .stdin(Stdio::null())
+            .stdout(writer.try_clone().context("Failed to clone the pipe")?)
+            .stderr(writer);
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+        let mut child = command
+            .spawn()
+            .with_context(|| format!("Failed to run `{}`", self))?;
+        let _tracked = TrackedTree::new(&child);
+        // Drop our handles to the write end of the pipe, otherwise reading would never end.
+        drop(command);
+        let output = std::thread::spawn(move || {
+            let mut output = Vec::new();
+            let _ = reader.read_to_end(&mut output);
+            String::from_utf8_lossy(&output).into_owned()
+        });
+
+        let start = Instant::now();
+        let mut killed = None;
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if let Some(limit) = timeouts.run_timeout {
+                if start.elapsed().as_secs() >= limit {
+                    kill_process_tree(&mut child)?;
+                    killed = Some(format!(
+                        "`{}` didn't complete within {} seconds",
+                        self, limit
+                    ));
+                    break child.wait()?;
+                }
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        };
+        Ok(StepReport {
+            command: self.to_string(),
+            exit_code: status.code(),
+            output: output.join().unwrap_or_default(),
+            killed,
+        })
+    }
+}
+
+impl fmt::Display for VerificationStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.command)?;
+        for arg in &self.args {
+            write!(f, " {}", arg)?;
+        }
+        Ok(())
+    }
+}
+
+/// The outcome of a verification step.
+#[derive(Debug)]
+pub struct StepReport {
+    pub command: String,
+    /// `None` if the command was terminated by a signal.
+    pub exit_code: Option<i32>,
+    /// `stdout` and `stderr`, interleaved.
+    pub output: String,
+    /// Why the command was killed before completing, if it was.
+    pub killed: Option<String>,
+}
+
+impl StepReport {
+    pub fn passed(&self) -> bool {
+        self.killed.is_none() && self.exit_code == Some(0)
+    }
+
+    pub fn render(&self, out: &mut String) {
+        let intro_msg = format!("🔘 Running `{}`", self.command);
+        writeln!(out, "{}", intro_msg.bold()).unwrap();
+        if self.passed() {
+            return;
+        }
+        if let Some(reason) = &self.killed {
+            writeln!(
+                out,
+                "{}",
+                format!("⏱️ {}: it was stopped", reason).bold().red()
+            )
+            .unwrap();
+        }
+        let status = match self.exit_code {
+            Some(code) => format!("exit code {}", code),
+            None => "no exit code".to_string(),
+        };
+        writeln!(
+            out,
+            "{}\n{}:\n{}",
+            format!("`{}` failed ({})", self.command, status)
+                .bold()
+                .red(),
+            "Output".bold(),
+            textwrap::indent(&self.output, "    ")
+        )
+        .unwrap();
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::VerificationStep;
+    use crate::timeout::Timeouts;
+    use std::collections::BTreeMap;
+    use std::time::{Duration, Instant};
+
+    fn shell(script: &str) -> VerificationStep {
+        VerificationStep {
+            command: "sh".into(),
+            args: vec!["-c".into(), script.into()],
+            env: BTreeMap::new(),
+            working_dir: None,
+        }
+    }
+
+    #[test]
+    fn steps_are_killed_after_the_run_timeout() {
+        let timeouts = Timeouts {
+            test_timeout: 60,
+            run_timeout: Some(1),
+        };
+        let start = Instant::now();
+        let report = shell("echo started; sleep 30")
+            .run(".".as_ref(), timeouts)
+            .unwrap();
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert!(!report.passed());
+        assert_eq!(report.output, "started\n");
+        assert_eq!(
+            report.killed.as_deref(),
+            Some("`sh -c echo started; sleep 30` didn't complete within 1 seconds")
+        );
+    }
+
+    #[test]
+    fn quiet_steps_are_not_killed() {
+        // `--test-timeout` only applies to tests.
+        let timeouts = Timeouts {
+            test_timeout: 1,
+            run_timeout: None,
+        };
+        let report = shell("sleep 2").run(".".as_ref(), timeouts).unwrap();
+        assert!(report.passed(), "{:?}", report);
+    }
+
+    #[test]
+    fn stdout_and_stderr_are_interleaved() {
+        let timeouts = Timeouts {
+            test_timeout: 60,
+            run_timeout: None,
+        };
+        let report = shell("echo compiling >&2; echo warning; echo error >&2; exit 1")
+            .run(".".as_ref(), timeouts)
+            .unwrap();
+        assert_eq!(report.exit_code, Some(1));
+        assert_eq!(report.output, "compiling\nwarning\nerror\n");
+    }
+}
@@ -0,0 +1,332 @@
+//! Wall-clock timeouts for `cargo test`.
+//!
+//! libtest only warns about tests that have been running for a long time, it never
+//! stops them: a test that hangs (e.g. waiting on a port that never opens) would block
+//! `ctr` forever. We watch the output of `cargo test` as it comes in and kill the whole
+//! process tree as soon as a test, or the run as a whole, exceeds its time limit.
+//!
+//! Per-test limits only hold with the `nightly` backend, where libtest reports when each
+//! test starts. The stable `pretty` output only reports tests as they complete, so there
+//! the limit bounds how long a test binary can go without completing a test: when it's
+//! exceeded, the tests that were still running are reported as interrupted, not as
+//! timed out, since we can't tell which of them was stuck.
+
+use crate::expectations::Expectations;
+use crate::libtest::test_binary;
+use crate::runner::TestId;
+use anyhow::Context;
+use once_cell::sync::Lazy;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Time limits set on the command line.
+#[derive(Debug, Clone, Copy, clap::Args)]
+pub struct Timeouts {
+    /// How long a single test can run, in seconds, unless `expectations.yml` says otherwise.
+    ///
+    /// Only enforced per test with the nightly backend. On stable, it's how long a test
+    /// binary can go without completing a test.
+    #[arg(long, value_name = "SECS", default_value_t = 60, global = true)]
+    pub test_timeout: u64,
+    /// How long `cargo test` can run for a single exercise, in seconds, compilation included.
+    /// It also bounds each of the other `.wr.toml` verification steps.
+    #[arg(long, value_name = "SECS", global = true)]
+    pub run_timeout: Option<u64>,
+}
+
+/// The time limits that apply to the tests of an exercise.
+#[derive(Debug)]
+pub struct TimeLimits {
+    run: Option<Duration>,
+    default_test: Duration,
+    /// The limit of each test listed in `expectations.yml`, keyed by its name there.
+    tests: Vec<(String, Duration)>,
+}
+
+impl TimeLimits {
+    /// Combine the command line limits with the `timeout_secs` fields in `expectations.yml`.
+    ///
+    /// The top-level `timeout_secs` replaces `--test-timeout` for every test of the exercise,
+    /// while the `timeout_secs` of an entry only applies to that test.
+    pub fn new(timeouts: Timeouts, expectations: &Expectations) -> Self {
+        let default_test = expectations.timeout_secs.unwrap_or(timeouts.test_timeout);
+        Self {
+            run: timeouts.run_timeout.map(Duration::from_secs),
+            default_test: Duration::from_secs(default_test),
+            tests: expectations
+                .tests
+                .iter()
+                .map(|test| {
+                    let timeout = test.timeout_secs.unwrap_or(default_test);
+                    (test.name.clone(), Duration::from_secs(timeout))
+                })
+                .collect(),
+        }
+    }
+
+    fn for_test(&self, path: &str) -> Duration {
+        let test_id = TestId {
+            binary: None,
+            path: path.to_owned(),
+        };
+        self.tests
+            .iter()
+            .find(|(name, _)| test_id.matches(name))
+            .map(|(_, timeout)| *timeout)
+            .unwrap_or(self.default_test)
+    }
+
+    /// The longest limit among the tests that haven't completed yet.
+    fn longest_pending(&self, completed: &HashSet<String>) -> Duration {
+        self.tests
+            .iter()
+            .filter(|(name, _)| {
+                !completed.iter().any(|path| {
+                    TestId {
+                        binary: None,
+                        path: path.to_owned(),
+                    }
+                    .matches(name)
+                })
+            })
+            .map(|(_, timeout)| *timeout)
+            .max()
+            .unwrap_or(self.default_test)
+    }
+}
+
+/// Keeps track of what `cargo test` is doing, based on its output, to tell when
+/// a time limit has been exceeded.
+///
+/// On the stable backend it can't enforce per-test limits: see the module docs.
+pub struct Watchdog<'a> {
+    limits: &'a TimeLimits,
+    json: bool,
+    start: Instant,
+    /// Tests that have started, but haven't completed yet.
+    running: HashMap<String, Instant>,
+    /// The test binary that is running and when it last showed signs of progress.
+    ///
+    /// The `pretty` format doesn't tell us when a test starts, only when it completes:
+    /// the best we can do is to check that *some* test completes within the limit of
+    /// the tests that are still pending.
+    binary: Option<(String, Instant)>,
+    /// Tests that have completed (`pretty` output only).
+    completed: HashSet<String>,
+}
+
+impl<'a> Watchdog<'a> {
+    pub fn new(limits: &'a TimeLimits, json: bool) -> Self {
+        Self {
+            limits,
+            json,
+            start: Instant::now(),
+            running: HashMap::new(),
+            binary: None,
+            completed: HashSet::new(),
+        }
+    }
+
+    /// Update the state of the run based on a line of `cargo test` output.
+    pub fn observe(&mut self, line: &str) {
+        let now = Instant::now();
+        if let Some(binary) = test_binary(line) {
+            self.binary = Some((binary.name, now));
+            return;
+        }
+        if self.json {
+            let Ok(event) = serde_json::from_str::<serde_json::Value>(line) else {
+                return;
+            };
+            if event.get("type").and_then(|t| t.as_str()) != Some("test") {
+                return;
+            }
+            let Some(name) = event.get("name").and_then(|n| n.as_str()) else {
+                return;
+            };
+            match event.get("event").and_then(|e| e.as_str()) {
+                Some("started") => {
+                    self.running.insert(name.to_owned(), now);
+                }
+                // libtest's own warning about a slow test: the test is still running.
+                Some("timeout") => {}
+                _ => {
+                    self.running.remove(name);
+                }
+            }
+        } else if line.starts_with("test result: ") {
+            self.binary = None;
+        } else if let Some((name, result)) = line
+            .strip_prefix("test ")
+            .and_then(|rest| rest.rsplit_once(" ..."))
+        {
+            let name = name.trim_end();
+            let name = name.strip_suffix(" - should panic").unwrap_or(name);
+            // With a single test thread, libtest announces each test when it starts.
+            if result.trim().is_empty() {
+                self.running.entry(name.to_owned()).or_insert(now);
+                return;
+            }
+            self.running.remove(name);
+            self.completed.insert(name.to_owned());
+            if let Some((_, last_progress)) = self.binary.as_mut() {
+                *last_progress = now;
+            }
+        }
+    }
+
+    /// If a time limit has been exceeded, explain which one.
+    pub fn check(&self) -> Option<String> {
+        if let Some(run) = self.limits.run {
+            if self.start.elapsed() > run {
+                return Some(format!(
+                    "`cargo test` didn't complete within {} seconds",
+                    run.as_secs()
+                ));
+            }
+        }
+        let mut overdue: Vec<_> = self
+            .running
+            .iter()
+            .filter(|(name, started)| started.elapsed() > self.limits.for_test(name))
+            .map(|(name, _)| name.as_str())
+            .collect();
+        if !overdue.is_empty() {
+            overdue.sort();
+            return Some(format!(
+                "{} exceeded {} time limit",
+                overdue
+                    .iter()
+                    .map(|name| format!("`{}`", name))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                if overdue.len() == 1 { "its" } else { "their" }
+            ));
+        }
+        if !self.json {
+            if let Some((binary, last_progress)) = &self.binary {
+                let limit = self.limits.longest_pending(&self.completed);
+                if last_progress.elapsed() > limit {
+                    return Some(format!(
+                        "No test in `{}` completed within {} seconds",
+                        binary,
+                        limit.as_secs()
+                    ));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// The process trees spawned by `ctr` that are still running, by the id of their root.
+///
+/// Each of them lives in its own process group, out of the terminal's foreground group:
+/// a Ctrl-C only reaches `ctr`, which has to kill them itself.
+static RUNNING_TREES: Lazy<Mutex<HashSet<u32>>> = Lazy::new(Default::default);
+
+/// Kill the process trees that are still running when `ctr` is interrupted (Ctrl-C,
+/// `SIGTERM`), then exit.
+pub fn kill_process_trees_on_interrupt() -> Result<(), anyhow::Error> {
+    ctrlc::set_handler(|| {
+        let trees = RUNNING_TREES.lock().unwrap_or_else(PoisonError::into_inner);
+        for pid in trees.iter() {
+            if let Err(e) = kill_process_group(*pid) {
+                eprintln!("{:?}", e);
+            }
+        }
+        std::process::exit(130);
+    })
+    .context("Failed to install the Ctrl-C handler")
+}
+
+/// Keeps a process tree in [`RUNNING_TREES`] for as long as it's in scope.
+pub struct TrackedTree(u32);
+
+impl TrackedTree {
+    pub fn new(child: &std::process::Child) -> Self {
+        RUNNING_TREES
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(child.id());
+        Self(child.id())
+    }
+}
+
+impl Drop for TrackedTree {
+    fn drop(&mut self) {
+        RUNNING_TREES
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&self.0);
+    }
+}
+
+/// Kill a process and all its descendants.
+///
+/// On Unix, the process must have been spawned as the leader of its own process group.
+pub fn kill_process_tree(child: &mut std::process::Child) -> Result<(), anyhow::Error> {
+    match kill_process_group(child.id()) {
+        // `taskkill` fails if the process has exited in the meantime.
+        Err(_) if cfg!(windows) && child.try_wait()?.is_some() => Ok(()),
+        result => result,
+    }
+}
+
+/// Kill the process group led by `pid` on Unix, the process tree rooted at `pid` on Windows.
+fn kill_process_group(pid: u32) -> Result<(), anyhow::Error> {
+    #[cfg(unix)]
+    {
+        let pgid = libc::pid_t::try_from(pid).context("The process id doesn't fit a `pid_t`")?;
+        // SAFETY: `killpg` only sends a signal, it doesn't touch our memory.
+        if unsafe { libc::killpg(pgid, libc::SIGKILL) } != 0 {
+            let error = std::io::Error::last_os_error();
+            // Every process in the group has already exited: there is nothing left to kill.
+            if error.raw_os_error() != Some(libc::ESRCH) {
+                return Err(error)
+                    .with_context(|| format!("Failed to kill process group {}", pgid));
+            }
+        }
+    }
+    #[cfg(windows)]
+    {
+        let status = std::process::Command::new("taskkill")
+            .args(["/F", "/T", "/PID", &pid.to_string()])
+            .status()
+            .context("Failed to run `taskkill`")?;
+        if !status.success() {
+            anyhow::bail!("`taskkill` failed to kill process {}: {}", pid, status);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TimeLimits, Watchdog};
+    use std::time::Duration;
+
+    #[test]
+    fn tests_are_overdue_once_they_exceed_their_own_limit() {
+        let limits = TimeLimits {
+            run: None,
+            default_test: Duration::from_secs(60),
+            tests: vec![
+                ("fast".into(), Duration::from_secs(60)),
+                ("slow".into(), Duration::ZERO),
+            ],
+        };
+        let mut watchdog = Watchdog::new(&limits, true);
+        watchdog.observe(r#"{ "type": "test", "event": "started", "name": "tests::fast" }"#);
+        assert_eq!(watchdog.check(), None);
+        watchdog.observe(r#"{ "type": "test", "event": "started", "name": "tests::slow" }"#);
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(
+            watchdog.check().as_deref(),
+            Some("`tests::slow` exceeded its time limit")
+        );
+        watchdog.observe(r#"{ "type": "test", "event": "ok", "name": "tests::slow" }"#);
+        assert_eq!(watchdog.check(), None);
+    }
+}
@@ -0,0 +1,817 @@
+//! Check the outcomes of `cargo test` against the expectations of an exercise.
+
+use crate::diagnostics::CompileError;
+use crate::expectations::{
+    matches_with_wildcards, Expectations, ExpectedCompileError, ExpectedKind, ExpectedOutcome,
+};
+use crate::harness::{verify_harness, HarnessOutcome, HarnessReport};
+use crate::normalize::Pipeline;
+use crate::runner::{run_tests, Backend, TestId, TestOutcome, TestRun, TestSelection};
+use crate::steps::{load_steps, StepReport, VerificationStep};
+use crate::timeout::{TimeLimits, Timeouts};
+use anyhow::Context;
+use owo_colors::OwoColorize;
+use pretty_assertions::StrComparison;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// The outcome of verifying a single exercise.
+#[derive(Debug)]
+pub struct ExerciseRun {
+    /// How we refer to the exercise in the output, e.g. its path relative to the workspace root.
+    pub name: String,
+    /// The directory of the exercise.
+    pub dir: PathBuf,
+    /// `Err` if we couldn't verify the exercise at all, e.g. because `expectations.yml` is invalid.
+    pub result: Result<ExerciseReport, anyhow::Error>,
+}
+
+impl ExerciseRun {
+    pub fn passed(&self) -> bool {
+        self.result.as_ref().is_ok_and(|report| report.passed())
+    }
+}
+
+/// The result of checking every test of an exercise against its expectations.
+#[derive(Debug)]
+pub struct ExerciseReport {
+    pub tests: Vec<TestReport>,
+    /// The `.wr.toml` verification steps that were executed, other than the tests.
+    pub steps: Vec<StepReport>,
+    /// Why `cargo test` was killed before completing, if it was.
+    pub killed: Option<String>,
+    /// Set if the tests failed to compile, or if they were expected to.
+    pub compile: Option<CompileReport>,
+    /// Set if `expectations.yml` describes a custom harness, rather than individual tests.
+    pub harness: Option<HarnessReport>,
+    /// Tests that `cargo test` ran, but that aren't listed in `expectations.yml`.
+    pub unlisted: Vec<UnlistedTest>,
+    /// Entries of `expectations.yml` that don't refer to any of the tests run by `cargo test`.
+    pub missing: Vec<MissingTest>,
+}
+
+impl ExerciseReport {
+    pub fn passed(&self) -> bool {
+        self.tests.iter().all(|test| test.mismatch.is_none())
+            && self.unlisted.is_empty()
+            && self.missing.is_empty()
+            && self.steps.iter().all(StepReport::passed)
+            && self
+                .compile
+                .as_ref()
+                .is_none_or(|compile| compile.mismatch.is_none())
+            && self.harness.as_ref().is_none_or(HarnessReport::passed)
+    }
+
+    /// Render the report in the format we show to humans on the terminal.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        if let Some(reason) = &self.killed {
+            writeln!(
+                out,
+                "{}",
+                format!("⏱️ {}: `cargo test` was stopped", reason)
+                    .bold()
+                    .red()
+            )
+            .unwrap();
+        }
+        if let Some(compile) = &self.compile {
+            compile.render(&mut out);
+        }
+        if let Some(harness) = &self.harness {
+            harness.render(&mut out);
+        }
+        for test in &self.tests {
+            test.render(&mut out);
+        }
+        self.render_listing(&mut out);
+        for step in &self.steps {
+            step.render(&mut out);
+        }
+        out
+    }
+
+    /// Render the differences between the tests run by `cargo test` and those listed
+    /// in `expectations.yml`.
+    pub fn render_listing(&self, out: &mut String) {
+        for test in &self.unlisted {
+            let msg = format!(
+                "❌ Test `{}` was run by `cargo test`, but it is not listed in `expectations.yml`",
+                test.test_id
+            );
+            writeln!(out, "{}", msg.bold().red()).unwrap();
+        }
+        for test in &self.missing {
+            let msg = match &test.kind {
+                Some(kind) => format!(
+                    "❌ There is no entry in `cargo test` output for a test named `{}` of kind `{}`",
+                    test.name, kind
+                ),
+                None => format!(
+                    "❌ There is no entry in `cargo test` output for a test named `{}`",
+                    test.name
+                ),
+            };
+            writeln!(out, "{}", msg.bold().red()).unwrap();
+        }
+        if !self.unlisted.is_empty() || !self.missing.is_empty() {
+            let hint = "Run `ctr --bless` to update `expectations.yml` with the tests that exist";
+            writeln!(out, "{}", hint.bold()).unwrap();
+        }
+    }
+}
+
+/// A test run by `cargo test` that has no entry in `expectations.yml`.
+#[derive(Debug)]
+pub struct UnlistedTest {
+    pub test_id: TestId,
+    /// The kind to add to its entry, if its name alone would be ambiguous.
+    pub kind: Option<ExpectedKind>,
+    pub actual: TestOutcome,
+}
+
+/// An entry of `expectations.yml` whose test wasn't run by `cargo test`.
+#[derive(Debug)]
+pub struct MissingTest {
+    pub name: String,
+    pub kind: Option<ExpectedKind>,
+    /// The position of the entry in the `tests` list of `expectations.yml`.
+    pub entry: usize,
+}
+
+/// The errors that prevented the tests from compiling, checked against `expected_compile_error`.
+#[derive(Debug)]
+pub struct CompileReport {
+    pub expected: Option<ExpectedCompileError>,
+    pub errors: Vec<CompileError>,
+    /// `None` if the tests compiled, or failed to compile, as expected.
+    pub mismatch: Option<CompileMismatch>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompileMismatch {
+    /// The tests failed to compile, but they were expected to compile.
+    UnexpectedCompileError,
+    /// The tests compiled, but they were expected not to.
+    MissingCompileError,
+    /// The tests failed to compile, but none of the errors is the expected one.
+    UnexpectedCompileErrorMessage,
+}
+
+impl CompileReport {
+    fn new(expected: Option<ExpectedCompileError>, errors: Vec<CompileError>) -> Self {
+        let mismatch = match &expected {
+            None => Some(CompileMismatch::UnexpectedCompileError),
+            Some(_) if errors.is_empty() => Some(CompileMismatch::MissingCompileError),
+            Some(expected) if errors.iter().any(|error| expected.matches(error)) => None,
+            Some(_) => Some(CompileMismatch::UnexpectedCompileErrorMessage),
+        };
+        Self {
+            expected,
+            errors,
+            mismatch,
+        }
+    }
+
+    pub fn render(&self, out: &mut String) {
+        let intro_msg = "🔘 Checking compilation against expectations";
+        writeln!(out, "{}", intro_msg.bold()).unwrap();
+        let Some(mismatch) = self.mismatch else {
+            return;
+        };
+        let failure_msg = match mismatch {
+            CompileMismatch::UnexpectedCompileError => "The tests failed to compile".to_string(),
+            CompileMismatch::MissingCompileError => format!(
+                "The tests compiled, but they were expected to fail with `{}`",
+                self.expected_message()
+            ),
+            CompileMismatch::UnexpectedCompileErrorMessage => format!(
+                "The tests failed to compile, but not with the error expected by `expectations.yml`: `{}`",
+                self.expected_message()
+            ),
+        };
+        writeln!(out, "{}", failure_msg.bold().red()).unwrap();
+        for error in &self.errors {
+            writeln!(
+                out,
+                "{}",
+                textwrap::indent(error.rendered.trim_end(), "    ")
+            )
+            .unwrap();
+        }
+    }
+
+    fn expected_message(&self) -> &str {
+        self.expected
+            .as_ref()
+            .map(|expected| expected.message.as_str())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug)]
+pub struct TestReport {
+    /// The name of the test, as written in `expectations.yml`.
+    pub name: String,
+    /// The position of the test in the `tests` list of `expectations.yml`.
+    /// It's not the position in `ExerciseReport::tests`, since some tests may be filtered out.
+    pub entry: usize,
+    pub test_id: TestId,
+    pub expected: ExpectedOutcome,
+    pub actual: TestOutcome,
+    /// `None` if the test behaved as expected.
+    pub mismatch: Option<Mismatch>,
+}
+
+/// The ways in which a test can fail to meet its expectations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mismatch {
+    UnexpectedSuccess,
+    UnexpectedFailure,
+    UnexpectedOutput,
+    /// The test was ignored, but it was expected to run.
+    UnexpectedIgnore,
+    /// The test ran, but it was expected to be ignored.
+    MissingIgnore,
+    MissingPanic,
+    UnexpectedPanicMessage,
+    Timeout,
+    /// The test didn't complete because `cargo test` was stopped.
+    Interrupted,
+}
+
+impl TestReport {
+    pub fn render(&self, out: &mut String) {
+        let intro_msg = format!("🔘 Checking test `{}` against expectations", self.name);
+        writeln!(out, "{}", intro_msg.bold()).unwrap();
+        let Some(mismatch) = self.mismatch else {
+            return;
+        };
+        match mismatch {
+            Mismatch::UnexpectedSuccess => {
+                writeln!(
+                    out,
+                    "{}",
+                    format!("Test `{}` succeeded, but was expected to fail", self.name)
+                        .bold()
+                        .red()
+                )
+                .unwrap();
+            }
+            Mismatch::UnexpectedFailure => {
+                writeln!(
+                    out,
+                    "{}\n{}:\n{}",
+                    format!("Test `{}` failed, but was expected to succeed.", self.name)
+                        .bold()
+                        .red(),
+                    "Raw test output:".bold(),
+                    textwrap::indent(self.raw_stdout(), "    ")
+                )
+                .unwrap();
+            }
+            Mismatch::UnexpectedOutput => {
+                let failure_msg_1 = format!("❌ `{}`", self.name);
+                let failure_msg_2 = "The test failed as expected, but the failure output doesn't match the expected output from `expectations.yml`.".to_string();
+                writeln!(
+                    out,
+                    "{}\n{}\n",
+                    failure_msg_1.red().bold(),
+                    failure_msg_2.red()
+                )
+                .unwrap();
+                writeln!(out, "{}", self.diff().unwrap_or_default()).unwrap();
+                writeln!(
+                    out,
+                    "{}\n{}",
+                    "Raw test output:".bold(),
+                    textwrap::indent(self.raw_stdout(), "    ")
+                )
+                .unwrap();
+            }
+            Mismatch::UnexpectedIgnore => {
+                writeln!(
+                    out,
+                    "{}",
+                    format!("Test `{}` was ignored, but was expected to run", self.name)
+                        .bold()
+                        .red()
+                )
+                .unwrap();
+            }
+            Mismatch::MissingIgnore => {
+                writeln!(
+                    out,
+                    "{}",
+                    format!("Test `{}` ran, but was expected to be ignored", self.name)
+                        .bold()
+                        .red()
+                )
+                .unwrap();
+            }
+            Mismatch::MissingPanic => {
+                writeln!(
+                    out,
+                    "{}",
+                    format!("Test `{}` was expected to panic, but it didn't.", self.name)
+                        .bold()
+                        .red()
+                )
+                .unwrap();
+                // Only failed tests come with their output.
+                if let TestOutcome::Failed { raw_stdout, .. } = &self.actual {
+                    writeln!(
+                        out,
+                        "{}:\n{}",
+                        "Raw test output:".bold(),
+                        textwrap::indent(raw_stdout, "    ")
+                    )
+                    .unwrap();
+                }
+            }
+            Mismatch::UnexpectedPanicMessage => {
+                let failure_msg_1 = format!("❌ `{}`", self.name);
+                let failure_msg_2 = "The test panicked as expected, but the panic message doesn't match the expected message from `expectations.yml`.".to_string();
+                writeln!(
+                    out,
+                    "{}\n{}\n",
+                    failure_msg_1.red().bold(),
+                    failure_msg_2.red()
+                )
+                .unwrap();
+                writeln!(out, "{}", self.diff().unwrap_or_default()).unwrap();
+            }
+            Mismatch::Timeout => {
+                writeln!(
+                    out,
+                    "{}",
+                    format!("Test `{}` timed out", self.name).bold().red()
+                )
+                .unwrap();
+            }
+            Mismatch::Interrupted => {
+                writeln!(
+                    out,
+                    "{}",
+                    format!(
+                        "Test `{}` didn't complete before `cargo test` was stopped",
+                        self.name
+                    )
+                    .bold()
+                    .red()
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    /// The unprocessed output of the test, if it failed.
+    pub fn raw_stdout(&self) -> &str {
+        match &self.actual {
+            TestOutcome::Failed { raw_stdout, .. } => raw_stdout,
+            _ => "",
+        }
+    }
+
+    /// The difference between the actual and the expected failure output (or panic message),
+    /// if they don't match.
+    pub fn diff(&self) -> Option<String> {
+        match (&self.actual, &self.expected) {
+            (TestOutcome::Failed { clean_stdout, .. }, ExpectedOutcome::Failure { output })
+                if !output.matches(clean_stdout) =>
+            {
+                Some(StrComparison::new(clean_stdout, output.as_str()).to_string())
+            }
+            (
+                TestOutcome::Ok {
+                    panic_message: Some(panic_message),
+                }
+                | TestOutcome::Failed {
+                    panic_message: Some(panic_message),
+                    ..
+                },
+                ExpectedOutcome::Panic { message },
+            ) if !matches_with_wildcards(message, panic_message) => {
+                Some(StrComparison::new(panic_message, message).to_string())
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Run the verification steps of the exercise in `exercise_dir`, checking the outcome of
+/// its tests against `expectations.yml`.
+///
+/// Without a `.wr.toml` file, the only step is `cargo test`.
+/// We stop at the first step that doesn't succeed.
+/// `selection` narrows down the tests run by the `cargo test` step.
+pub fn verify_exercise(
+    exercise_dir: &Path,
+    backend: Backend,
+    selection: &TestSelection,
+    timeouts: Timeouts,
+) -> Result<ExerciseReport, anyhow::Error> {
+    let wr_steps = load_steps(exercise_dir)?;
+    let mut expectations = if wr_steps.is_none() || exercise_dir.join("expectations.yml").is_file()
+    {
+        Some(Expectations::load(exercise_dir)?)
+    } else {
+        None
+    };
+    let mut steps = wr_steps.unwrap_or_default();
+    if steps.is_empty()
+        || (expectations.is_some() && !steps.iter().any(VerificationStep::is_cargo_test))
+    {
+        steps.push(VerificationStep::cargo_test());
+    }
+
+    let mut report = ExerciseReport {
+        tests: Vec::new(),
+        steps: Vec::new(),
+        killed: None,
+        compile: None,
+        harness: None,
+        unlisted: Vec::new(),
+        missing: Vec::new(),
+    };
+    for step in &steps {
+        if step.is_cargo_test() {
+            if let Some(expectations) = expectations.take() {
+                let checked = if expectations.harness.is_some() {
+                    check_harness(exercise_dir, step, expectations, selection, timeouts)?
+                } else {
+                    check_expectations(
+                        exercise_dir,
+                        step,
+                        expectations,
+                        backend,
+                        selection,
+                        timeouts,
+                    )?
+                };
+                report.tests = checked.tests;
+                report.killed = checked.killed;
+                report.compile = checked.compile;
+                report.harness = checked.harness;
+                report.unlisted = checked.unlisted;
+                report.missing = checked.missing;
+                if !report.passed() {
+                    break;
+                }
+                continue;
+            }
+        }
+        let step_report = if step.is_cargo_test() {
+            step.with_selection(selection).run(exercise_dir, timeouts)?
+        } else {
+            step.run(exercise_dir, timeouts)?
+        };
+        let passed = step_report.passed();
+        report.steps.push(step_report);
+        if !passed {
+            break;
+        }
+    }
+    Ok(report)
+}
+
+/// Build and run the custom harness described in `expectations.yml`, and check how it behaves.
+///
+/// The time limit for tests applies to each run of the harness as a whole.
+fn check_harness(
+    exercise_dir: &Path,
+    step: &VerificationStep,
+    mut expectations: Expectations,
+    selection: &TestSelection,
+    timeouts: Timeouts,
+) -> Result<ExerciseReport, anyhow::Error> {
+    let harness = expectations
+        .harness
+        .take()
+        .expect("We only check harnesses that are described in `expectations.yml`");
+    if !expectations.tests.is_empty() {
+        anyhow::bail!(
+            "`expectations.yml` can't describe both a `harness` and its `tests`.\n\
+            This is a bug in the workshop, please report it to the instructor!"
+        );
+    }
+    let pipeline = Pipeline::new(&expectations.normalize, exercise_dir);
+    let timeout = Duration::from_secs(expectations.timeout_secs.unwrap_or(timeouts.test_timeout));
+    let outcome = verify_harness(
+        &step.dir(exercise_dir),
+        &step.env,
+        harness,
+        selection,
+        timeout,
+        &pipeline,
+    )?;
+    let (compile_errors, harness) = match outcome {
+        HarnessOutcome::CompileErrors(errors) => (errors, None),
+        HarnessOutcome::Ran(harness) => (Vec::new(), Some(*harness)),
+    };
+    let compile = (!compile_errors.is_empty() || expectations.expected_compile_error.is_some())
+        .then(|| CompileReport::new(expectations.expected_compile_error, compile_errors));
+    Ok(ExerciseReport {
+        tests: Vec::new(),
+        steps: Vec::new(),
+        killed: None,
+        compile,
+        harness,
+        unlisted: Vec::new(),
+        missing: Vec::new(),
+    })
+}
+
+/// Run the tests of the exercise in `exercise_dir` and check them against `expectations`.
+///
+/// If the tests don't compile, there is nothing else to check: we only report
+/// the compile errors.
+fn check_expectations(
+    exercise_dir: &Path,
+    step: &VerificationStep,
+    expectations: Expectations,
+    backend: Backend,
+    selection: &TestSelection,
+    timeouts: Timeouts,
+) -> Result<ExerciseReport, anyhow::Error> {
+    let limits = TimeLimits::new(timeouts, &expectations);
+    let TestRun {
+        mut outcomes,
+        killed,
+        compile_errors,
+        exit_code,
+        output,
+    } = run_tests(
+        &step.dir(exercise_dir),
+        &step.env,
+        backend,
+        selection,
+        &limits,
+    )
+    .context("Failed to run tests")?;
+    if !compile_errors.is_empty() || expectations.expected_compile_error.is_some() {
+        return Ok(ExerciseReport {
+            tests: Vec::new(),
+            steps: Vec::new(),
+            killed,
+            compile: Some(CompileReport::new(
+                expectations.expected_compile_error,
+                compile_errors,
+            )),
+            harness: None,
+            unlisted: Vec::new(),
+            missing: Vec::new(),
+        });
+    }
+    if outcomes.is_empty() && killed.is_none() && exit_code != Some(0) {
+        let status = match exit_code {
+            Some(code) => format!("exit code {}", code),
+            None => "no exit code".to_string(),
+        };
+        // Skip the JSON messages from the compiler, they're not meant for humans.
+        let output: String = output
+            .lines()
+            .filter(|line| !line.starts_with("{\"reason\":"))
+            .map(|line| format!("    {}\n", line))
+            .collect();
+        anyhow::bail!(
+            "`cargo test` failed ({}) before running any test.\nOutput:\n{}",
+            status,
+            output
+        );
+    }
+    let pipeline = Pipeline::new(&expectations.normalize, exercise_dir);
+    for outcome in outcomes.values_mut() {
+        outcome.normalize(&pipeline);
+    }
+
+    // Map each entry in `expectations.yml` to the test it refers to.
+    // Short names are convenient, but they must not be ambiguous: we never want to
+    // silently pick one of several tests that happen to share the same name.
+    let mut resolved: HashMap<TestId, &str> = HashMap::new();
+    let mut test_ids = Vec::with_capacity(expectations.tests.len());
+    let mut missing = Vec::new();
+    for (entry, test) in expectations.tests.iter().enumerate() {
+        let mut candidates: Vec<_> = outcomes
+            .keys()
+            .filter(|id| id.matches(&test.name))
+            .filter(|id| {
+                test.kind
+                    .as_ref()
+                    .is_none_or(|kind| kind.matches(id.binary.as_ref()))
+            })
+            .collect();
+        candidates.sort();
+        let test_id = match candidates.as_slice() {
+            // The test was left out by the filters passed to `cargo test`.
+            [] if selection.is_filtered() => {
+                test_ids.push(None);
+                continue;
+            }
+            // The test didn't get a chance to report its outcome before we stopped `cargo test`.
+            [] if killed.is_some() => {
+                let test_id = TestId {
+                    binary: None,
+                    path: test.name.clone(),
+                };
+                outcomes.insert(test_id.clone(), TestOutcome::Interrupted);
+                test_id
+            }
+            [] => {
+                missing.push(MissingTest {
+                    name: test.name.clone(),
+                    kind: test.kind.clone(),
+                    entry,
+                });
+                test_ids.push(None);
+                continue;
+            }
+            [test_id] => (*test_id).clone(),
+            _ => anyhow::bail!(
+                "The test name `{}` in `expectations.yml` is ambiguous, it matches:\n{}\n\
+                Use a fully qualified name, or set its `kind`, to pick one of them.\n\
+                This is a bug in the workshop, please report it to the instructor!",
+                &test.name,
+                candidates
+                    .iter()
+                    .map(|id| format!("- `{}`", id))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ),
+        };
+        if let Some(other_name) = resolved.insert(test_id.clone(), &test.name) {
+            anyhow::bail!(
+                "Both `{}` and `{}` in `expectations.yml` refer to the same test, `{}`.\n\
+                This is a bug in the workshop, please report it to the instructor!",
+                other_name,
+                &test.name,
+                test_id
+            );
+        }
+        test_ids.push(Some(test_id));
+    }
+
+    // Exhaustiveness check: the list of tests in `expectations.yml` should match the list of tests
+    // that `cargo test` ran.
+    let mut discovered_tests: Vec<_> = outcomes
+        .keys()
+        .filter(|test_id| !resolved.contains_key(test_id))
+        .cloned()
+        .collect();
+    discovered_tests.sort();
+    let unlisted: Vec<_> = discovered_tests
+        .into_iter()
+        .map(|test_id| {
+            // The name must pick this test, and this test only.
+            let ambiguous = outcomes
+                .keys()
+                .any(|other| other != &test_id && other.matches(&test_id.path));
+            UnlistedTest {
+                kind: test_id
+                    .binary
+                    .as_ref()
+                    .filter(|_| ambiguous)
+                    .map(ExpectedKind::of),
+                actual: outcomes
+                    .remove(&test_id)
+                    .expect("Every test has an outcome"),
+                test_id,
+            }
+        })
+        .collect();
+
+    if selection.is_filtered() && !test_ids.is_empty() && test_ids.iter().all(Option::is_none) {
+        anyhow::bail!("None of the tests listed in `expectations.yml` matches the test selection")
+    }
+
+    let tests = expectations
+        .tests
+        .into_iter()
+        .zip(test_ids)
+        .enumerate()
+        .filter_map(|(entry, (test, test_id))| Some((entry, test, test_id?)))
+        .map(|(entry, test, test_id)| {
+            let actual = outcomes
+                .remove(&test_id)
+                .expect("Every resolved test has an outcome");
+            let mismatch = check(&test.outcome, &actual);
+            TestReport {
+                name: test.name,
+                entry,
+                test_id,
+                expected: test.outcome,
+                actual,
+                mismatch,
+            }
+        })
+        .collect();
+    Ok(ExerciseReport {
+        tests,
+        steps: Vec::new(),
+        killed,
+        compile: None,
+        harness: None,
+        unlisted,
+        missing,
+    })
+}
+
+fn check(expected: &ExpectedOutcome, actual: &TestOutcome) -> Option<Mismatch> {
+    match (actual, expected) {
+        (TestOutcome::Timeout, _) => Some(Mismatch::Timeout),
+        (TestOutcome::Interrupted, _) => Some(Mismatch::Interrupted),
+        (TestOutcome::Ignored, ExpectedOutcome::Ignored) => None,
+        (TestOutcome::Ignored, _) => Some(Mismatch::UnexpectedIgnore),
+        (_, ExpectedOutcome::Ignored) => Some(Mismatch::MissingIgnore),
+        (TestOutcome::Ok { .. }, ExpectedOutcome::Success) => None,
+        (TestOutcome::Ok { .. }, ExpectedOutcome::Failure { .. }) => {
+            Some(Mismatch::UnexpectedSuccess)
+        }
+        (TestOutcome::Failed { .. }, ExpectedOutcome::Success) => Some(Mismatch::UnexpectedFailure),
+        (TestOutcome::Failed { clean_stdout, .. }, ExpectedOutcome::Failure { output }) => {
+            (!output.matches(clean_stdout)).then_some(Mismatch::UnexpectedOutput)
+        }
+        (
+            TestOutcome::Ok { panic_message } | TestOutcome::Failed { panic_message, .. },
+            ExpectedOutcome::Panic { message },
+        ) => match panic_message {
+            None => Some(Mismatch::MissingPanic),
+            Some(panic_message) => (!matches_with_wildcards(message, panic_message))
+                .then_some(Mismatch::UnexpectedPanicMessage),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, Mismatch, TestReport};
+    use crate::expectations::ExpectedOutcome;
+    use crate::runner::{TestId, TestOutcome};
+
+    #[test]
+    fn panic_expectations_need_a_panic_with_the_expected_message() {
+        let any_panic = ExpectedOutcome::Panic {
+            message: "[..]".into(),
+        };
+        let passed = |panic_message: Option<&str>| TestOutcome::Ok {
+            panic_message: panic_message.map(str::to_owned),
+        };
+        // A test that passed without a panic of its own isn't a `#[should_panic]` test.
+        assert_eq!(
+            check(&any_panic, &passed(None)),
+            Some(Mismatch::MissingPanic)
+        );
+        assert_eq!(check(&any_panic, &passed(Some("Boom"))), None);
+
+        let boom = ExpectedOutcome::Panic {
+            message: "Boom".into(),
+        };
+        assert_eq!(
+            check(&boom, &passed(Some("Bang"))),
+            Some(Mismatch::UnexpectedPanicMessage)
+        );
+        let failed = TestOutcome::Failed {
+            clean_stdout: "Boom".into(),
+            raw_stdout: "Boom".into(),
+            panic_message: None,
+        };
+        assert_eq!(check(&boom, &failed), Some(Mismatch::MissingPanic));
+    }
+
+    #[test]
+    fn a_missing_panic_shows_the_output_of_failed_tests() {
+        let render = |actual: TestOutcome| {
+            let report = TestReport {
+                name: "boom".into(),
+                entry: 0,
+                test_id: TestId {
+                    binary: None,
+                    path: "tests::boom".into(),
+                },
+                expected: ExpectedOutcome::Panic {
+                    message: "Boom".into(),
+                },
+                actual,
+                mismatch: Some(Mismatch::MissingPanic),
+            };
+            let mut out = String::new();
+            report.render(&mut out);
+            out
+        };
+
+        let passed = render(TestOutcome::Ok {
+            panic_message: None,
+        });
+        assert!(!passed.contains("Raw test output"), "{}", passed);
+        let failed = render(TestOutcome::Failed {
+            clean_stdout: "Error: \"Not found\"".into(),
+            raw_stdout: "Error: \"Not found\"".into(),
+            panic_message: None,
+        });
+        assert!(failed.contains("Raw test output"), "{}", failed);
+        assert!(failed.contains("Error: \"Not found\""), "{}", failed);
+    }
+}
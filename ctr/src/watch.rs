@@ -0,0 +1,342 @@
+//! Re-verify an exercise every time one of its files changes.
+//!
+//! Once the exercise behaves as expected, we move on to the next one, following
+//! the order of the chapters in `book/src/SUMMARY.md`.
+
+use crate::progress;
+use crate::runner::{Backend, TestSelection};
+use crate::timeout::Timeouts;
+use crate::verify::{verify_exercise, ExerciseReport, ExerciseRun};
+use crate::workspace::{display_name, exercise_crates, exercise_order, exercise_sources};
+use anyhow::Context;
+use notify::event::ModifyKind;
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use owo_colors::OwoColorize;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// How long to wait for things to settle down after a change, e.g. when an editor
+/// saves several files at once or replaces a file with a renamed temporary copy.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Watch the exercise in `exercise_dir`, then the ones after it, until they all pass.
+pub fn watch(
+    workspace_root: Option<&Path>,
+    exercise_dir: &Path,
+    backend: Backend,
+    selection: &TestSelection,
+    timeouts: Timeouts,
+) -> Result<(), anyhow::Error> {
+    let order = match workspace_root {
+        Some(root) => exercise_order(root)?,
+        None => Vec::new(),
+    };
+    let mut exercise = fs_err::canonicalize(exercise_dir)?;
+    loop {
+        let name = match workspace_root {
+            Some(root) => display_name(root, &exercise),
+            None => exercise.display().to_string(),
+        };
+        watch_exercise(
+            workspace_root,
+            &exercise,
+            &name,
+            backend,
+            selection,
+            timeouts,
+        )?;
+
+        let next = order
+            .iter()
+            .position(|e| e == &exercise)
+            .and_then(|i| order.get(i + 1));
+        let Some(next) = next else {
+            println!("{}", format!("🎉 `{}` behaves as expected!", name).bold());
+            return Ok(());
+        };
+        let next_name = match workspace_root {
+            Some(root) => display_name(root, next),
+            None => next.display().to_string(),
+        };
+        println!(
+            "{}",
+            format!(
+                "✅ `{}` behaves as expected! Moving on to `{}`",
+                name, next_name
+            )
+            .bold()
+            .green()
+        );
+        exercise = next.clone();
+    }
+}
+
+/// Verify the exercise every time one of its files changes, until it behaves as expected.
+fn watch_exercise(
+    workspace_root: Option<&Path>,
+    exercise_dir: &Path,
+    name: &str,
+    backend: Backend,
+    selection: &TestSelection,
+    timeouts: Timeouts,
+) -> Result<(), anyhow::Error> {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher =
+        notify::recommended_watcher(tx).context("Failed to start the file watcher")?;
+    // We watch whole directories, rather than the individual files, to keep track of
+    // files that are replaced rather than modified in place (as many editors do).
+    // Crates shared with other exercises live outside of the exercise directory.
+    let shared_crates = exercise_crates(exercise_dir)?
+        .into_iter()
+        .filter(|crate_dir| !crate_dir.starts_with(exercise_dir));
+    for dir in std::iter::once(exercise_dir.to_owned()).chain(shared_crates) {
+        watcher
+            .watch(&dir, RecursiveMode::Recursive)
+            .with_context(|| format!("Failed to watch `{}`", dir.display()))?;
+    }
+
+    println!("{}", format!("👀 Watching `{}`", name).bold());
+    let mut previous: Option<Verdicts> = None;
+    loop {
+        let run = ExerciseRun {
+            name: name.to_owned(),
+            dir: exercise_dir.to_owned(),
+            result: verify_exercise(exercise_dir, backend, selection, timeouts),
+        };
+        // A partial run doesn't tell us whether the exercise is completed.
+        if let Some(root) = workspace_root.filter(|_| !selection.is_filtered()) {
+            progress::record_runs(root, std::slice::from_ref(&run))?;
+        }
+        match &run.result {
+            Ok(report) => {
+                print!("{}", render_changes(previous.as_ref(), report));
+                if report.passed() {
+                    return Ok(());
+                }
+                previous = Some(Verdicts::new(report));
+            }
+            Err(e) => {
+                println!("{:?}", e);
+                previous = None;
+            }
+        }
+        wait_for_changes(&rx, exercise_dir)?;
+        println!(
+            "\n{}",
+            format!("🔄 Change detected, verifying `{}` again", name).bold()
+        );
+    }
+}
+
+fn wait_for_changes(
+    rx: &mpsc::Receiver<notify::Result<Event>>,
+    exercise_dir: &Path,
+) -> Result<(), anyhow::Error> {
+    // Computed before waiting, so that a new path dependency is picked up after the
+    // change to `Cargo.toml` that introduced it.
+    let sources = exercise_sources(exercise_dir)?;
+    let is_change = |event: notify::Result<Event>| -> Result<bool, anyhow::Error> {
+        let event = event.context("Failed to watch for changes")?;
+        Ok(is_relevant(&event, &sources))
+    };
+    while !is_change(rx.recv().context("The file watcher stopped")?)? {}
+    // Debounce: wait until no more changes come in.
+    loop {
+        match rx.recv_timeout(DEBOUNCE) {
+            Ok(event) => {
+                is_change(event)?;
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => return Ok(()),
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                anyhow::bail!("The file watcher stopped")
+            }
+        }
+    }
+}
+
+/// Whether `event` is a change to one of the files that affect the outcome of the tests,
+/// i.e. one of the `sources` of the exercise or a file inside them.
+///
+/// Files are read while the tests run, so we only care about events that change
+/// their content (reads can update their access time).
+fn is_relevant(event: &Event, sources: &[PathBuf]) -> bool {
+    match event.kind {
+        EventKind::Modify(ModifyKind::Metadata(_)) => return false,
+        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_) => {}
+        _ => return false,
+    }
+    event.paths.iter().any(|path| {
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        sources.iter().any(|source| path.starts_with(source))
+            // `insta` writes pending snapshots next to the accepted ones.
+            && !file_name.ends_with(".snap.new")
+            && !file_name.ends_with(".pending-snap")
+    })
+}
+
+/// Whether each test and each verification step behaved as expected.
+struct Verdicts {
+    /// The rendered report of each test, so that a test that keeps failing with
+    /// a different diff counts as a change.
+    tests: HashMap<String, String>,
+    steps: HashMap<String, bool>,
+}
+
+impl Verdicts {
+    fn new(report: &ExerciseReport) -> Self {
+        Self {
+            tests: report
+                .tests
+                .iter()
+                .map(|test| {
+                    let mut rendered = String::new();
+                    test.render(&mut rendered);
+                    (test.name.clone(), rendered)
+                })
+                .collect(),
+            steps: report
+                .steps
+                .iter()
+                .map(|step| (step.command.clone(), step.passed()))
+                .collect(),
+        }
+    }
+}
+
+/// Render the verdicts that changed since the previous run, or the whole report
+/// if there is nothing to compare against.
+fn render_changes(previous: Option<&Verdicts>, report: &ExerciseReport) -> String {
+    let Some(previous) = previous else {
+        return report.render();
+    };
+    let mut out = String::new();
+    if let Some(reason) = &report.killed {
+        writeln!(out, "{}", format!("⏱️ {}", reason).bold().red()).unwrap();
+    }
+    if let Some(compile) = &report.compile {
+        compile.render(&mut out);
+    }
+    if let Some(harness) = &report.harness {
+        harness.render(&mut out);
+    }
+    for test in &report.tests {
+        let mut rendered = String::new();
+        test.render(&mut rendered);
+        if previous.tests.get(&test.name) == Some(&rendered) {
+            continue;
+        }
+        if test.mismatch.is_none() {
+            let msg = format!("✅ `{}` now behaves as expected", test.name);
+            writeln!(out, "{}", msg.green()).unwrap();
+        } else {
+            out.push_str(&rendered);
+        }
+    }
+    report.render_listing(&mut out);
+    for step in &report.steps {
+        if previous.steps.get(&step.command) != Some(&step.passed()) {
+            step.render(&mut out);
+        }
+    }
+    let n_passed = report
+        .tests
+        .iter()
+        .filter(|test| test.mismatch.is_none())
+        .count();
+    writeln!(
+        out,
+        "{}",
+        format!(
+            "{}/{} tests behave as expected",
+            n_passed,
+            report.tests.len()
+        )
+        .bold()
+    )
+    .unwrap();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expectations::ExpectedOutcome;
+    use crate::runner::{TestId, TestOutcome};
+    use crate::verify::{Mismatch, TestReport};
+    use notify::event::DataChange;
+
+    fn failing(output: &str) -> ExerciseReport {
+        let test = TestReport {
+            name: "happy".into(),
+            entry: 0,
+            test_id: TestId {
+                binary: None,
+                path: "tests::happy".into(),
+            },
+            expected: ExpectedOutcome::Success,
+            actual: TestOutcome::Failed {
+                clean_stdout: output.into(),
+                raw_stdout: output.into(),
+                panic_message: None,
+            },
+            mismatch: Some(Mismatch::UnexpectedFailure),
+        };
+        ExerciseReport {
+            tests: vec![test],
+            steps: vec![],
+            killed: None,
+            compile: None,
+            harness: None,
+            unlisted: Vec::new(),
+            missing: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn a_different_failure_is_a_change() {
+        let previous = Verdicts::new(&failing("left: 1"));
+
+        let same = render_changes(Some(&previous), &failing("left: 1"));
+        assert!(!same.contains("left: 1"), "{}", same);
+
+        let different = render_changes(Some(&previous), &failing("left: 2"));
+        assert!(different.contains("left: 2"), "{}", different);
+    }
+
+    #[test]
+    fn edits_to_nested_crates_are_relevant() {
+        let exercise = tempfile::tempdir().unwrap();
+        let dir = fs_err::canonicalize(exercise.path()).unwrap();
+        fs_err::create_dir_all(dir.join("macros/src")).unwrap();
+        fs_err::write(
+            dir.join("Cargo.toml"),
+            "[package]\nname = \"exercise\"\n\n[dependencies]\nmacros = { path = \"macros\" }\n",
+        )
+        .unwrap();
+        fs_err::write(
+            dir.join("macros/Cargo.toml"),
+            "[package]\nname = \"macros\"\n",
+        )
+        .unwrap();
+        let sources = exercise_sources(&dir).unwrap();
+        let edit = |path: &str| {
+            Event::new(EventKind::Modify(ModifyKind::Data(DataChange::Content)))
+                .add_path(dir.join(path))
+        };
+
+        assert!(is_relevant(&edit("macros/src/lib.rs"), &sources));
+        assert!(is_relevant(&edit("src/lib.rs"), &sources));
+        assert!(!is_relevant(
+            &edit("macros/target/debug/build.log"),
+            &sources
+        ));
+        assert!(!is_relevant(&edit("target/debug/build.log"), &sources));
+        assert!(!is_relevant(&edit("snapshots/a.snap.new"), &sources));
+    }
+}
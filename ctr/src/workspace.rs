@@ -0,0 +1,513 @@
+//! Verify every exercise in the workspace in a single invocation.
+
+use crate::runner::{Backend, TestSelection};
+use crate::timeout::Timeouts;
+use crate::verify::{verify_exercise, ExerciseRun};
+use anyhow::Context;
+use owo_colors::OwoColorize;
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+#[derive(Debug, serde::Deserialize)]
+struct Manifest {
+    workspace: Option<WorkspaceSection>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct WorkspaceSection {
+    #[serde(default)]
+    members: Vec<String>,
+    #[serde(default)]
+    exclude: Vec<String>,
+}
+
+/// The files and directories, relative to the directory of an exercise (or of a crate
+/// it depends on), that can affect the outcome of its tests.
+const EXERCISE_SOURCES: &[&str] = &[
+    "src",
+    "tests",
+    "snapshots",
+    "build.rs",
+    "Cargo.toml",
+    "expectations.yml",
+    ".wr.toml",
+];
+
+/// The files and directories that can affect the outcome of the tests of the exercise
+/// in `exercise_dir`, i.e. the [`EXERCISE_SOURCES`] of each of its [crates](exercise_crates).
+pub fn exercise_sources(exercise_dir: &Path) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let sources = exercise_crates(exercise_dir)?
+        .into_iter()
+        .flat_map(|crate_dir| {
+            EXERCISE_SOURCES
+                .iter()
+                .map(move |entry| crate_dir.join(entry))
+        })
+        .collect();
+    Ok(sources)
+}
+
+/// The exercise in `exercise_dir`, followed by the crates of the workspace it depends on
+/// through a `path` dependency, directly or not.
+///
+/// Those are the crates nested in the exercise directory (e.g. a procedural macro crate,
+/// since that's where learners write their code in some exercises) and the ones shared
+/// by several exercises. Crates nested in the exercise directory are returned as paths
+/// that start with `exercise_dir`, the others as canonical paths.
+pub fn exercise_crates(exercise_dir: &Path) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let root = fs_err::canonicalize(exercise_dir)?;
+    // Dependencies outside of the workspace are not part of the course.
+    let workspace_root = find_workspace_root(&root).unwrap_or_else(|_| root.clone());
+    let mut seen = HashSet::from([root.clone()]);
+    let mut pending = vec![root.clone()];
+    let mut crates = Vec::new();
+    while let Some(crate_dir) = pending.pop() {
+        crates.push(match crate_dir.strip_prefix(&root) {
+            Ok(relative) => exercise_dir.join(relative),
+            Err(_) => crate_dir.clone(),
+        });
+        let manifest_path = crate_dir.join("Cargo.toml");
+        if !manifest_path.is_file() {
+            continue;
+        }
+        for path in path_dependencies(&manifest_path)? {
+            let Ok(dependency_dir) = fs_err::canonicalize(crate_dir.join(path)) else {
+                continue;
+            };
+            if dependency_dir.starts_with(&workspace_root) && seen.insert(dependency_dir.clone()) {
+                pending.push(dependency_dir);
+            }
+        }
+    }
+    Ok(crates)
+}
+
+/// The `path` of every dependency in the manifest, target-specific ones included.
+fn path_dependencies(manifest_path: &Path) -> Result<Vec<String>, anyhow::Error> {
+    let raw_manifest = fs_err::read_to_string(manifest_path)?;
+    let manifest: toml::Table = toml::from_str(&raw_manifest)
+        .with_context(|| format!("Failed to parse `{}`", manifest_path.display()))?;
+    let targets = manifest
+        .get("target")
+        .and_then(toml::Value::as_table)
+        .into_iter()
+        .flat_map(|targets| targets.values().filter_map(toml::Value::as_table));
+    let paths = std::iter::once(&manifest)
+        .chain(targets)
+        .flat_map(|table| {
+            ["dependencies", "dev-dependencies", "build-dependencies"]
+                .into_iter()
+                .filter_map(|section| table.get(section).and_then(toml::Value::as_table))
+        })
+        .flat_map(|dependencies| dependencies.values())
+        .filter_map(|dependency| dependency.get("path").and_then(toml::Value::as_str))
+        .map(str::to_owned)
+        .collect();
+    Ok(paths)
+}
+
+/// Find the root of the workspace that contains `start`, i.e. the closest ancestor
+/// with a `Cargo.toml` that has a `[workspace]` section.
+pub fn find_workspace_root(start: &Path) -> Result<PathBuf, anyhow::Error> {
+    for dir in start.ancestors() {
+        let manifest_path = dir.join("Cargo.toml");
+        if !manifest_path.is_file() {
+            continue;
+        }
+        if read_manifest(&manifest_path)?.workspace.is_some() {
+            return Ok(dir.to_owned());
+        }
+    }
+    anyhow::bail!("`{}` is not inside a Cargo workspace", start.display())
+}
+
+fn read_manifest(manifest_path: &Path) -> Result<Manifest, anyhow::Error> {
+    let raw_manifest = fs_err::read_to_string(manifest_path)?;
+    toml::from_str(&raw_manifest)
+        .with_context(|| format!("Failed to parse `{}`", manifest_path.display()))
+}
+
+/// Discover all workspace members that come with an `expectations.yml` file.
+///
+/// Members are listed in the `[workspace]` section of the root `Cargo.toml`, either
+/// as plain paths or as glob patterns (e.g. `exercises/*/*`).
+pub fn discover_exercises(workspace_root: &Path) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let manifest = read_manifest(&workspace_root.join("Cargo.toml"))?;
+    let workspace = manifest
+        .workspace
+        .context("The root `Cargo.toml` doesn't have a `[workspace]` section")?;
+    let excluded: Vec<PathBuf> = workspace
+        .exclude
+        .iter()
+        .map(|path| workspace_root.join(path))
+        .collect();
+
+    let mut exercises = Vec::new();
+    for member in &workspace.members {
+        let pattern = workspace_root.join(member);
+        let pattern = pattern
+            .to_str()
+            .with_context(|| format!("The workspace member `{}` is not valid UTF-8", member))?;
+        for path in glob::glob(pattern)
+            .with_context(|| format!("Invalid workspace member pattern: `{}`", member))?
+        {
+            let path = path?;
+            if path.join("expectations.yml").is_file() && !excluded.contains(&path) {
+                exercises.push(path);
+            }
+        }
+    }
+    exercises.sort();
+    exercises.dedup();
+    Ok(exercises)
+}
+
+/// The exercises of the workshop, in the order they appear in `book/src/SUMMARY.md`.
+///
+/// Each chapter of the book (`<section>/<chapter>.md`) comes with an exercise
+/// in `exercises/<section>/<chapter>`.
+pub fn exercise_order(workspace_root: &Path) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let summary_path = workspace_root.join("book/src/SUMMARY.md");
+    if !summary_path.is_file() {
+        return Ok(Vec::new());
+    }
+    let summary =
+        fs_err::read_to_string(&summary_path).context("Failed to read `book/src/SUMMARY.md`")?;
+    let link = regex::Regex::new(r"\]\(([^)]+)\.md\)").expect("Failed to compile regex");
+    let exercises = summary
+        .lines()
+        .filter_map(|line| link.captures(line))
+        .map(|captures| workspace_root.join("exercises").join(&captures[1]))
+        .filter_map(|exercise| fs_err::canonicalize(exercise).ok())
+        .filter(|exercise| exercise.is_dir())
+        .collect();
+    Ok(exercises)
+}
+
+/// Verify every exercise in the workspace, using up to `jobs` exercises in parallel.
+///
+/// We print a line as soon as each exercise completes, followed by the detailed
+/// report of each failed exercise and a summary table once they are all done.
+pub fn verify_workspace(
+    workspace_root: &Path,
+    backend: Backend,
+    selection: &TestSelection,
+    timeouts: Timeouts,
+    jobs: usize,
+) -> Result<Vec<ExerciseRun>, anyhow::Error> {
+    let exercises = discover_exercises(workspace_root)?;
+
+    let runs = in_parallel(&exercises, jobs, |exercise| {
+        let run = ExerciseRun {
+            name: display_name(workspace_root, exercise),
+            dir: exercise.to_owned(),
+            result: verify_exercise(exercise, backend, selection, timeouts),
+        };
+        if run.passed() {
+            println!("✅ {}", run.name);
+        } else {
+            println!("❌ {}", run.name.red());
+        }
+        run
+    });
+
+    for run in &runs {
+        let details = match &run.result {
+            Ok(report) if report.passed() => continue,
+            Ok(report) => report.render(),
+            Err(e) => format!("{:?}\n", e),
+        };
+        println!("\n{}\n{}", format!("━━━ {} ━━━", run.name).bold(), details);
+    }
+    print!("{}", render_summary(&runs));
+    Ok(runs)
+}
+
+/// Apply `f` to every item, using up to `jobs` threads, and return the results
+/// in the same order as the items.
+pub fn in_parallel<T: Sync, R: Send>(
+    items: &[T],
+    jobs: usize,
+    f: impl Fn(&T) -> R + Sync,
+) -> Vec<R> {
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<R>>> = Mutex::new(items.iter().map(|_| None).collect());
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.max(1) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::SeqCst);
+                let Some(item) = items.get(i) else {
+                    break;
+                };
+                let result = f(item);
+                results.lock().unwrap()[i] = Some(result);
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|result| result.expect("Every item has been processed"))
+        .collect()
+}
+
+/// How we refer to an exercise in the output: its path relative to the workspace root.
+pub fn display_name(workspace_root: &Path, exercise: &Path) -> String {
+    exercise
+        .strip_prefix(workspace_root)
+        .unwrap_or(exercise)
+        .display()
+        .to_string()
+}
+
+/// A table with the outcome of every exercise.
+fn render_summary(runs: &[ExerciseRun]) -> String {
+    let rows: Vec<_> = runs
+        .iter()
+        .map(|run| {
+            let (status, tests) = match &run.result {
+                // A custom harness is checked as a whole, not test by test.
+                Ok(report) if report.harness.is_some() => {
+                    let status = if report.passed() { "pass" } else { "FAIL" };
+                    (status, "harness".to_string())
+                }
+                Ok(report) => {
+                    let passed = report
+                        .tests
+                        .iter()
+                        .filter(|test| test.mismatch.is_none())
+                        .count();
+                    let status = if report.passed() { "pass" } else { "FAIL" };
+                    (status, format!("{}/{}", passed, report.tests.len()))
+                }
+                Err(_) => ("ERROR", "-".to_string()),
+            };
+            (run.name.as_str(), status, tests)
+        })
+        .collect();
+    let name_width = rows
+        .iter()
+        .map(|(name, _, _)| name.len())
+        .chain(["Exercise".len()])
+        .max()
+        .unwrap_or_default();
+    let mut out = String::new();
+    writeln!(
+        out,
+        "\n{}",
+        format!("{:<name_width$}  {:<6}  {}", "Exercise", "Result", "Tests").bold()
+    )
+    .unwrap();
+    for (name, status, tests) in rows {
+        let status_cell = format!("{:<6}", status);
+        let status_cell = if status == "pass" {
+            status_cell.green().to_string()
+        } else {
+            status_cell.red().bold().to_string()
+        };
+        writeln!(out, "{:<name_width$}  {}  {}", name, status_cell, tests).unwrap();
+    }
+    writeln!(out).unwrap();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expectations::ExpectedOutcome;
+    use crate::normalize::strip_ansi;
+    use crate::runner::{TestId, TestOutcome};
+    use crate::verify::{ExerciseReport, Mismatch, TestReport};
+
+    /// Create the files at `paths` in `dir`, along with their parent directories.
+    fn create_files(dir: &Path, paths: &[&str]) {
+        for path in paths {
+            let path = dir.join(path);
+            fs_err::create_dir_all(path.parent().unwrap()).unwrap();
+            fs_err::write(path, "").unwrap();
+        }
+    }
+
+    fn report(mismatches: &[Option<Mismatch>]) -> ExerciseReport {
+        let tests = mismatches
+            .iter()
+            .enumerate()
+            .map(|(entry, mismatch)| TestReport {
+                name: format!("test_{}", entry),
+                entry,
+                test_id: TestId {
+                    binary: None,
+                    path: format!("tests::test_{}", entry),
+                },
+                expected: ExpectedOutcome::Success,
+                actual: TestOutcome::Ok {
+                    panic_message: None,
+                },
+                mismatch: *mismatch,
+            })
+            .collect();
+        ExerciseReport {
+            tests,
+            steps: vec![],
+            killed: None,
+            compile: None,
+            harness: None,
+            unlisted: Vec::new(),
+            missing: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn exercises_are_discovered_from_the_workspace_members() {
+        let workspace = tempfile::tempdir().unwrap();
+        let root = fs_err::canonicalize(workspace.path()).unwrap();
+        fs_err::write(
+            root.join("Cargo.toml"),
+            r#"[workspace]
+members = ["exercises/*/*", "exercises/01_intro/00_welcome", "tools/checker"]
+exclude = ["exercises/01_intro/01_excluded"]
+"#,
+        )
+        .unwrap();
+        create_files(
+            &root,
+            &[
+                "exercises/01_intro/00_welcome/expectations.yml",
+                "exercises/01_intro/01_excluded/expectations.yml",
+                "exercises/02_mocks/00_intro/Cargo.toml",
+                "exercises/02_mocks/01_traits/expectations.yml",
+                "tools/checker/expectations.yml",
+            ],
+        );
+
+        assert_eq!(
+            discover_exercises(&root).unwrap(),
+            [
+                root.join("exercises/01_intro/00_welcome"),
+                root.join("exercises/02_mocks/01_traits"),
+                root.join("tools/checker"),
+            ]
+        );
+    }
+
+    #[test]
+    fn exercises_follow_the_order_of_the_book() {
+        let workspace = tempfile::tempdir().unwrap();
+        let root = fs_err::canonicalize(workspace.path()).unwrap();
+        assert!(exercise_order(&root).unwrap().is_empty());
+
+        create_files(
+            &root,
+            &[
+                "exercises/02_mocks/00_intro/Cargo.toml",
+                "exercises/01_intro/00_welcome/Cargo.toml",
+                "book/src/SUMMARY.md",
+            ],
+        );
+        fs_err::write(
+            root.join("book/src/SUMMARY.md"),
+            "# Summary\n\n\
+            - [Welcome](01_intro/00_welcome.md)\n\
+            - [Mocks](02_mocks/README.md)\n\
+            \x20 - [Intro](02_mocks/00_intro.md)\n",
+        )
+        .unwrap();
+
+        // Chapters without an exercise directory are skipped.
+        assert_eq!(
+            exercise_order(&root).unwrap(),
+            [
+                root.join("exercises/01_intro/00_welcome"),
+                root.join("exercises/02_mocks/00_intro"),
+            ]
+        );
+    }
+
+    #[test]
+    fn crates_shared_through_the_workspace_are_followed() {
+        let workspace = tempfile::tempdir().unwrap();
+        let root = fs_err::canonicalize(workspace.path()).unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        let outside = fs_err::canonicalize(outside.path()).unwrap();
+        fs_err::write(root.join("Cargo.toml"), "[workspace]\n").unwrap();
+        let exercise = root.join("exercises/01_macros/00_intro");
+        fs_err::create_dir_all(exercise.join("macros")).unwrap();
+        fs_err::create_dir_all(root.join("exercises/01_macros/helpers")).unwrap();
+        fs_err::write(
+            exercise.join("Cargo.toml"),
+            format!(
+                "[dependencies]\nmacros = {{ path = \"macros\" }}\n\
+                elsewhere = {{ path = {:?} }}\n",
+                outside.display().to_string()
+            ),
+        )
+        .unwrap();
+        fs_err::write(
+            exercise.join("macros/Cargo.toml"),
+            "[dependencies]\nhelpers = { path = \"../../helpers\" }\n",
+        )
+        .unwrap();
+
+        // Crates outside of the workspace are not part of the course.
+        assert_eq!(
+            exercise_crates(&exercise).unwrap(),
+            [
+                exercise.clone(),
+                exercise.join("macros"),
+                root.join("exercises/01_macros/helpers"),
+            ]
+        );
+    }
+
+    #[test]
+    fn results_are_in_the_order_of_the_items() {
+        let items: Vec<u64> = (0..10).collect();
+        let expected: Vec<u64> = items.iter().map(|i| i * 2).collect();
+        for jobs in [0, 1, 3, 50] {
+            let results = in_parallel(&items, jobs, |i| {
+                // Make the first items take longer, so that they complete last.
+                std::thread::sleep(std::time::Duration::from_millis(10 - i));
+                i * 2
+            });
+            assert_eq!(results, expected, "jobs = {}", jobs);
+        }
+    }
+
+    #[test]
+    fn the_summary_has_a_row_per_exercise() {
+        let run = |name: &str, result| ExerciseRun {
+            name: name.into(),
+            dir: PathBuf::from(name),
+            result,
+        };
+        let runs = [
+            run("exercises/01_intro/00_welcome", Ok(report(&[None, None]))),
+            run(
+                "exercises/02_mocks/01_traits",
+                Ok(report(&[None, Some(Mismatch::UnexpectedFailure)])),
+            ),
+            run(
+                "exercises/02_mocks/02_mockall",
+                Err(anyhow::anyhow!("Invalid `expectations.yml`")),
+            ),
+        ];
+
+        let summary = strip_ansi(&render_summary(&runs));
+        let lines: Vec<&str> = summary.lines().map(str::trim_end).collect();
+        assert_eq!(
+            lines,
+            [
+                "",
+                "Exercise                       Result  Tests",
+                "exercises/01_intro/00_welcome  pass    2/2",
+                "exercises/02_mocks/01_traits   FAIL    1/2",
+                "exercises/02_mocks/02_mockall  ERROR   -",
+                "",
+            ]
+        );
+    }
+}
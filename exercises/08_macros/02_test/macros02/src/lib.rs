@@ -32,6 +32,23 @@ pub fn vanilla_test(_args: TkStream, input: TkStream) -> TkStream {
     .into()
 }
 
+/// Turn a function with parameters into one test per case.
+///
+/// Cases are listed in the attribute, `#[cases((301, true), not_found(404, false))]`,
+/// or each in a `#[case(...)]` attribute below it, `#[case(301, true)]` and
+/// `#[case::not_found(404, false)]`. `(301, true)` on `fn is_redirect(code: u16, expected: bool)`
+/// becomes `is_redirect_case_1_301_true`, `not_found(404, false)` becomes `is_redirect_not_found`.
+/// `should_panic` and `ignore` can be set for a single case, after a semicolon:
+/// `#[case(0, false; should_panic)]`.
+#[proc_macro_attribute]
+pub fn cases(args: TkStream, input: TkStream) -> TkStream {
+    let test_fn: ItemFn = syn::parse_macro_input!(input as ItemFn);
+    // There are no fixtures here: every case provides all the parameters.
+    macros_cases::expand(args.into(), test_fn, "macros02::cases", |_| false)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
 fn is_test_attribute(attr: &Attribute) -> bool {
     attr.path().get_ident().map_or(false, |id| id.eq("test"))
 }
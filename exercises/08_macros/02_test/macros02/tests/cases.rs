@@ -0,0 +1,25 @@
+use macros02::cases;
+
+fn is_redirect(code: u16) -> bool {
+    assert!((100..600).contains(&code), "Invalid status code: {}", code);
+    (300..400).contains(&code)
+}
+
+#[cases((301, true), see_other(303, true))]
+#[case(200, false)]
+#[case::not_found(404, false)]
+#[case(0, false; should_panic(expected = "Invalid status code: 0"))]
+#[case(600, false; ignore = "not a valid status code")]
+fn redirects(code: u16, expected: bool) {
+    assert_eq!(is_redirect(code), expected);
+}
+
+mod nested {
+    use macros02::cases;
+
+    // `super::` still means the parent of `nested`.
+    #[cases((302, true))]
+    fn redirects(code: u16, expected: bool) {
+        assert_eq!(super::is_redirect(code), expected);
+    }
+}
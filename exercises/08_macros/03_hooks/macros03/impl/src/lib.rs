@@ -0,0 +1,732 @@
+use macros_cases::{is_case, is_test_attribute, list_of_keys, parse_cases, unknown_key_error};
+use proc_macro::TokenStream;
+use proc_macro2::{Spacing, Span, TokenStream as TokenStream2, TokenTree};
+use quote::{quote, quote_spanned, ToTokens};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{
+    Attribute, Expr, ExprCall, ExprLit, FnArg, Ident, Item, ItemFn, ItemMod, Lit, Pat, PatType,
+    ReturnType, Token, Type,
+};
+
+#[proc_macro_attribute]
+pub fn test(args: TokenStream, input: TokenStream) -> TokenStream {
+    let test_fn: ItemFn = syn::parse_macro_input!(input as ItemFn);
+    let Args { before, after } = match syn::parse_macro_input!(args as RawArgs)
+        .validate(&["before", "after"], &["before"])
+        .map(Args::new)
+    {
+        Ok(args) => args,
+        Err(e) => return e.to_compile_error().into(),
+    };
+
+    let ItemFn {
+        attrs,
+        vis,
+        mut sig,
+        block,
+    } = test_fn;
+
+    let test_name = sig.ident.to_string();
+    let output_type = match &sig.output {
+        ReturnType::Default => quote! { () },
+        ReturnType::Type(_, ty) => quote! { #ty },
+    };
+    // Our own variables are invisible to the test body, thanks to mixed-site hygiene.
+    let context = Ident::new("__macros03_context", Span::mixed_site());
+    let output = Ident::new("__macros03_output", Span::mixed_site());
+    let after_failure = Ident::new("__macros03_after_failure", Span::mixed_site());
+
+    let is_async = sig.asyncness.is_some();
+    let has_test_attribute = attrs.iter().any(is_test_attribute);
+    if is_async && !has_test_attribute {
+        return syn::Error::new_spanned(
+            sig.asyncness,
+            "`async` tests need a runtime: add `#[tokio::test]` (or a similar attribute) \
+            below `#[macros03::test]`",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    // Parameters are fixtures, unless the attribute that registers the test provides
+    // them itself (e.g. `#[sqlx::test]`).
+    let takes_fixtures = attrs
+        .iter()
+        .filter(|attr| is_test_attribute(attr))
+        .all(registers_parameterless_tests);
+    let fixtures = if takes_fixtures {
+        let inputs = std::mem::take(&mut sig.inputs);
+        match resolve_fixtures(inputs, &quote! { &#context }) {
+            Ok(fixtures) => fixtures,
+            Err(e) => return e.to_compile_error().into(),
+        }
+    } else {
+        Vec::new()
+    };
+    let fixture_bindings = fixtures.iter().map(|(pat_type, value)| {
+        quote! { let #pat_type = #value; }
+    });
+
+    let before_calls = before.iter().map(|hook| {
+        let path = &hook.path;
+        let path_str = path_to_string(path);
+        let binding = match &hook.binding {
+            Some(pat) => quote! { #pat },
+            None => quote! { _ },
+        };
+        let call = if is_async {
+            quote! { ::macros03::AsyncHook::call(#path, &#context).await }
+        } else {
+            quote! { ::macros03::Hook::call(#path, &#context) }
+        };
+        quote! {
+            let #binding = ::macros03::__private::check_before_hook(#call, #path_str);
+        }
+    });
+    let after_calls = after.iter().map(|path| {
+        let path_str = path_to_string(path);
+        if is_async {
+            quote! {
+                ::macros03::__private::run_async_after_hook(
+                    #path, &#context, #path_str, &mut #after_failure
+                ).await;
+            }
+        } else {
+            quote! {
+                ::macros03::__private::run_after_hook(#path, &#context, #path_str, &mut #after_failure);
+            }
+        }
+    });
+
+    // The `after` hooks must run however the test ends: we catch panics in the `before`
+    // hooks and in the body, and resume them with their original payload once the hooks
+    // are done.
+    // The original body runs in a closure (or an `async` block) of its own, so that
+    // `return` and `?` only leave the body.
+    // Fixtures are dropped in the reverse order of their creation, right after the body.
+    let guarded = if is_async {
+        quote! {
+            ::macros03::__private::CatchUnwind::new(async {
+                #(#before_calls)*
+                #(#fixture_bindings)*
+
+                ::macros03::__private::async_body::<#output_type, _>(async #block).await
+            })
+            .await
+        }
+    } else {
+        quote! {
+            ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(
+                || -> #output_type {
+                    #(#before_calls)*
+                    #(#fixture_bindings)*
+
+                    #[allow(clippy::redundant_closure_call)]
+                    (|| -> #output_type #block)()
+                },
+            ))
+        }
+    };
+
+    let mut output = quote::quote! {
+        #(#attrs)*
+        #vis #sig
+        {
+            let #context = ::macros03::TestContext::new(#test_name, ::core::module_path!());
+
+            let #output = #guarded;
+
+            let mut #after_failure = ::core::option::Option::None;
+            #(#after_calls)*
+
+            ::macros03::__private::finish(#output, #after_failure)
+        }
+    };
+
+    // Runtime attributes (`#[tokio::test]`, `#[sqlx::test]`, ...) and `#[googletest::gtest]`
+    // register the test themselves, and must expand after us to see the hooks.
+    if !has_test_attribute {
+        output = {
+            quote! {
+                #[::core::prelude::v1::test]
+                #output
+            }
+        };
+    }
+    output.into()
+}
+
+/// Turn a function into a fixture, which tests and other fixtures can take as a parameter.
+///
+/// Its parameters are fixtures too. They are created anew for every fixture that
+/// depends on them, rather than shared with the test or with other fixtures.
+#[proc_macro_attribute]
+pub fn fixture(args: TokenStream, input: TokenStream) -> TokenStream {
+    let fixture_fn: ItemFn = syn::parse_macro_input!(input as ItemFn);
+    let args = TokenStream2::from(args);
+    if !args.is_empty() {
+        return syn::Error::new_spanned(args, "`#[fixture]` doesn't take arguments")
+            .to_compile_error()
+            .into();
+    }
+    let ItemFn {
+        attrs,
+        vis,
+        mut sig,
+        block,
+    } = fixture_fn;
+
+    let error = if sig.asyncness.is_some() {
+        Some(syn::Error::new_spanned(
+            sig.asyncness,
+            "fixtures can't be `async`: use a `before` hook to bind an `async` value",
+        ))
+    } else if !sig.generics.params.is_empty() {
+        Some(syn::Error::new_spanned(
+            &sig.generics,
+            "fixtures can't be generic: the type they return picks them",
+        ))
+    } else if sig.output == ReturnType::Default {
+        Some(syn::Error::new_spanned(
+            &sig.ident,
+            "fixtures must return a value: use a `before` hook for setup code",
+        ))
+    } else {
+        None
+    };
+    if let Some(error) = error {
+        return error.to_compile_error().into();
+    }
+    let ReturnType::Type(_, output_type) = &sig.output else {
+        unreachable!()
+    };
+
+    let ctx = Ident::new("__macros03_context", Span::mixed_site());
+    let inputs = std::mem::take(&mut sig.inputs);
+    let fixtures = match resolve_fixtures(inputs, &quote! { #ctx }) {
+        Ok(fixtures) => fixtures,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    sig.inputs = fixtures
+        .iter()
+        .map(|(pat_type, _)| FnArg::Typed(pat_type.clone()))
+        .collect();
+    let values = fixtures.iter().map(|(_, value)| value);
+
+    // The function keeps its name: the provider is a struct with the same name, which
+    // lives in the type namespace as long as it has braces.
+    let name = &sig.ident;
+    quote! {
+        #(#attrs)*
+        #vis #sig #block
+
+        #[allow(non_camel_case_types)]
+        #[doc = concat!("The provider of the `", stringify!(#name), "` fixture.")]
+        #vis struct #name {}
+
+        impl ::macros03::Fixture for #name {
+            type Output = #output_type;
+
+            fn create(#ctx: &::macros03::TestContext) -> #output_type {
+                #name(#(#values),*)
+            }
+        }
+
+        impl ::macros03::ProvidedBy<#name> for #output_type {}
+    }
+    .into()
+}
+
+/// Turn a function with parameters into one test per case.
+///
+/// Cases are listed in the attribute, `#[cases((301, true), not_found(404, false))]`,
+/// or each in a `#[case(...)]` attribute below it, `#[case(301, true)]` and
+/// `#[case::not_found(404, false)]`. Both can be combined.
+/// Each case provides a value for every parameter, or only for the first ones if
+/// `#[macros03::test]` provides the others as fixtures.
+/// The tests sit next to each other, named after the function: `(301, true)` on
+/// `fn is_redirect(code: u16, expected: bool)` becomes `is_redirect_case_1_301_true`,
+/// while `not_found(404, false)` becomes `is_redirect_not_found`.
+/// `should_panic` and `ignore` can be set for a single case, after a semicolon:
+/// `#[case(0, false; should_panic(expected = "Invalid status code"))]`.
+#[proc_macro_attribute]
+pub fn cases(args: TokenStream, input: TokenStream) -> TokenStream {
+    let test_fn: ItemFn = syn::parse_macro_input!(input as ItemFn);
+    // Without `#[macros03::test]`, nothing would provide the parameters left as fixtures.
+    macros_cases::expand(args.into(), test_fn, "macros03::cases", is_macros03_test)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Attach hooks to every test of an inline module.
+///
+/// `before_each` and `after_each` behave like `before` and `after` in `#[test]`.
+/// `before_all` and `after_all` run once for the whole module, before its first test
+/// and after its last one. They take no arguments.
+#[proc_macro_attribute]
+pub fn suite(args: TokenStream, input: TokenStream) -> TokenStream {
+    let module: ItemMod = syn::parse_macro_input!(input as ItemMod);
+    let hooks = match syn::parse_macro_input!(args as RawArgs).validate(
+        &["before_each", "after_each", "before_all", "after_all"],
+        &["before_each"],
+    ) {
+        Ok(hooks) => hooks,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let ItemMod {
+        attrs,
+        vis,
+        unsafety,
+        mod_token,
+        ident,
+        content,
+        semi,
+    } = module;
+    let Some((brace, mut items)) = content else {
+        return syn::Error::new_spanned(
+            semi,
+            "`#[macros03::suite]` needs the tests inline: `mod tests { ... }`",
+        )
+        .to_compile_error()
+        .into();
+    };
+    let hook = |key: &str| hooks.iter().find(|hook| hook.key == key);
+
+    let each = {
+        let before = hook("before_each").map(|hook| {
+            let path = &hook.path;
+            match &hook.binding {
+                Some(pat) => quote! { before = #path -> #pat, },
+                None => quote! { before = #path, },
+            }
+        });
+        let after = hook("after_each").map(|hook| {
+            let path = &hook.path;
+            quote! { after = #path }
+        });
+        (before.is_some() || after.is_some())
+            .then(|| syn::parse_quote! { #[::macros03::test(#before #after)] })
+    };
+
+    // `before_all` and `after_all` are type-erased by a pair of functions, which the `Suite`
+    // calls at the right time. Each test enters the suite first, and leaves it last.
+    let suite = Ident::new("__MACROS03_SUITE", Span::mixed_site());
+    let enter = Ident::new("__macros03_enter", Span::mixed_site());
+    let leave = Ident::new("__macros03_leave", Span::mixed_site());
+    let suite_hook = |key: &str| match hook(key) {
+        Some(hook) => {
+            let path = &hook.path;
+            let path_str = path_to_string(path);
+            quote! { ::macros03::__private::run_suite_hook(#path, #key, #path_str) }
+        }
+        None => quote! { ::core::result::Result::Ok(()) },
+    };
+    let all = (hook("before_all").is_some() || hook("after_all").is_some()).then(|| {
+        let before_all = suite_hook("before_all");
+        let after_all = suite_hook("after_all");
+        let attribute: Attribute =
+            syn::parse_quote! { #[::macros03::test(before = #enter, after = #leave)] };
+        (attribute, before_all, after_all)
+    });
+
+    let mut n_tests = 0usize;
+    for item in &mut items {
+        let Item::Fn(test_fn) = item else {
+            continue;
+        };
+        // `#[cases]` copies the remaining attributes to every test it generates.
+        if let Some(cases_attr) = test_fn.attrs.iter().find(|attr| is_cases_attribute(attr)) {
+            n_tests += count_cases(cases_attr, &test_fn.attrs, &test_fn.sig);
+        } else if test_fn.attrs.iter().any(is_test_attribute) {
+            n_tests += 1;
+        } else {
+            continue;
+        }
+        // Attributes expand from the outermost to the innermost one, and the last one to
+        // expand wraps all the others: the suite's hooks go right after the test's own
+        // `#[macros03::test]`, if any, and before the attribute that registers the test.
+        let position = match test_fn.attrs.iter().rposition(is_macros03_test) {
+            Some(position) => position + 1,
+            None => test_fn
+                .attrs
+                .iter()
+                .position(is_test_attribute)
+                .unwrap_or(test_fn.attrs.len()),
+        };
+        let suite_attrs = each
+            .iter()
+            .chain(all.as_ref().map(|(attribute, _, _)| attribute));
+        test_fn
+            .attrs
+            .splice(position..position, suite_attrs.cloned());
+    }
+
+    if let Some((_, before_all, after_all)) = all {
+        let suite_items: [Item; 3] = [
+            syn::parse_quote! {
+                static #suite: ::macros03::__private::Suite = ::macros03::__private::Suite::new(
+                    #n_tests,
+                    || #before_all,
+                    || #after_all,
+                );
+            },
+            syn::parse_quote! {
+                fn #enter() {
+                    #suite.enter();
+                }
+            },
+            syn::parse_quote! {
+                fn #leave() {
+                    #suite.leave();
+                }
+            },
+        ];
+        items.extend(suite_items);
+    }
+
+    let module = ItemMod {
+        attrs,
+        vis,
+        unsafety,
+        mod_token,
+        ident,
+        content: Some((brace, items)),
+        semi,
+    };
+    module.into_token_stream().into()
+}
+
+/// Work out how each parameter gets its value, given an expression for the `&TestContext`.
+///
+/// `&TestContext` parameters get the context, `#[from(fixture)]` picks a fixture by name,
+/// otherwise the type of the parameter picks the fixture.
+fn resolve_fixtures(
+    inputs: Punctuated<FnArg, Token![,]>,
+    ctx: &TokenStream2,
+) -> syn::Result<Vec<(PatType, TokenStream2)>> {
+    let mut fixtures = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let mut pat_type = match input {
+            FnArg::Typed(pat_type) => pat_type,
+            FnArg::Receiver(receiver) => {
+                return Err(syn::Error::new_spanned(
+                    receiver,
+                    "tests and fixtures can't take `self`",
+                ))
+            }
+        };
+        let mut from = None;
+        for attr in std::mem::take(&mut pat_type.attrs) {
+            if attr.path().is_ident("from") {
+                if from.is_some() {
+                    return Err(syn::Error::new_spanned(
+                        attr,
+                        "a parameter can only come from one fixture",
+                    ));
+                }
+                from = Some(attr.parse_args_with(|input: ParseStream| {
+                    input.parse::<syn::Path>().map_err(|e| {
+                        syn::Error::new(e.span(), "expected the name of a fixture: `#[from(db)]`")
+                    })
+                })?);
+            } else {
+                pat_type.attrs.push(attr);
+            }
+        }
+
+        let ty = &pat_type.ty;
+        let value = match from {
+            Some(path) => quote_spanned! { path.span()=>
+                <#path as ::macros03::Fixture>::create(#ctx)
+            },
+            None if is_context(ty) => quote! { #ctx },
+            None => quote_spanned! { ty.span()=>
+                ::macros03::__private::provide::<#ty, _>(#ctx)
+            },
+        };
+        fixtures.push((pat_type, value));
+    }
+    Ok(fixtures)
+}
+
+/// `&TestContext`, `&macros03::TestContext`, ...
+fn is_context(ty: &Type) -> bool {
+    let Type::Reference(reference) = ty else {
+        return false;
+    };
+    let Type::Path(path) = &*reference.elem else {
+        return false;
+    };
+    path.path
+        .segments
+        .last()
+        .is_some_and(|segment| segment.ident == "TestContext")
+}
+
+struct RawArgs {
+    vars: Vec<RawHook>,
+}
+
+/// Argument parsing goes through two phases:
+///
+/// 1. Parse the raw arguments into a struct, which is syntactically what we expect
+/// 2. Validate the arguments and convert them into the form we want (semantic validation)
+struct Args {
+    before: Option<BeforeHook>,
+    after: Option<syn::Path>,
+}
+
+/// A `before` hook, optionally binding the value it returns: `before = setup -> fixture`.
+struct BeforeHook {
+    path: syn::Path,
+    binding: Option<Pat>,
+}
+
+/// A hook that passed validation: `key = path -> binding`.
+struct ValidHook {
+    key: Ident,
+    path: syn::Path,
+    binding: Option<Pat>,
+}
+
+impl Args {
+    fn new(hooks: Vec<ValidHook>) -> Self {
+        let mut args = Args {
+            before: None,
+            after: None,
+        };
+        for hook in hooks {
+            if hook.key == "before" {
+                args.before = Some(BeforeHook {
+                    path: hook.path,
+                    binding: hook.binding,
+                });
+            } else {
+                args.after = Some(hook.path);
+            }
+        }
+        args
+    }
+}
+
+impl RawArgs {
+    /// Check the arguments against the `keys` that the attribute accepts.
+    /// Only the hooks in `bindable` can bind the value they return.
+    ///
+    /// Every problem is reported at once, each one spanned on the offending tokens.
+    fn validate(self, keys: &[&str], bindable: &[&str]) -> syn::Result<Vec<ValidHook>> {
+        let mut hooks: Vec<ValidHook> = Vec::with_capacity(keys.len());
+        let mut seen: Vec<Ident> = Vec::with_capacity(keys.len());
+        let mut errors: Option<syn::Error> = None;
+        let mut report = |error: syn::Error| match &mut errors {
+            Some(errors) => errors.combine(error),
+            None => errors = Some(error),
+        };
+
+        for varg in self.vars {
+            if !keys.iter().any(|key| varg.type_ == key) {
+                report(unknown_key_error(&varg.type_, keys));
+                continue;
+            }
+            if let Some(first) = seen.iter().find(|key| **key == varg.type_) {
+                let mut error = syn::Error::new(
+                    varg.type_.span(),
+                    format!(
+                        "`{}` can only be set once: call the other hooks from a single function",
+                        varg.type_
+                    ),
+                );
+                error.combine(syn::Error::new(
+                    first.span(),
+                    format!("`{}` is first set here", first),
+                ));
+                report(error);
+                continue;
+            }
+            seen.push(varg.type_.clone());
+
+            let path = match parse_hook_path(varg.value) {
+                Ok(path) => path,
+                Err(e) => {
+                    report(e);
+                    continue;
+                }
+            };
+            if let Some(binding) = &varg.binding {
+                if !bindable.iter().any(|key| varg.type_ == key) {
+                    report(syn::Error::new_spanned(
+                        binding,
+                        format!(
+                            "only {} hooks can bind the value they return",
+                            list_of_keys(bindable, "and")
+                        ),
+                    ));
+                    continue;
+                }
+            }
+            hooks.push(ValidHook {
+                key: varg.type_,
+                path,
+                binding: varg.binding,
+            });
+        }
+
+        match errors {
+            Some(errors) => Err(errors),
+            None => Ok(hooks),
+        }
+    }
+}
+
+/// The value of a hook must be the path of a function, e.g. `setup` or `fixtures::setup`.
+///
+/// We try to explain what's wrong with the common mistakes.
+fn parse_hook_path(value: TokenStream2) -> syn::Result<syn::Path> {
+    if let Ok(path) = syn::parse2::<syn::Path>(value.clone()) {
+        return Ok(path);
+    }
+    let message = match syn::parse2::<Expr>(value.clone()) {
+        Ok(Expr::Lit(ExprLit {
+            lit: Lit::Str(lit), ..
+        })) => format!(
+            "expected the path of a function, found a string literal: try `{}`, without quotes",
+            lit.value()
+        ),
+        Ok(Expr::Call(ExprCall { func, .. })) if matches!(*func, Expr::Path(_)) => format!(
+            "expected the path of a function, found a call: try `{}`, without parentheses",
+            func.to_token_stream()
+        ),
+        Ok(Expr::Closure(_)) => {
+            "closures can't be used as hooks: move the code into a function and pass its path"
+                .to_string()
+        }
+        _ => "expected the path of a function, e.g. `setup` or `fixtures::setup`".to_string(),
+    };
+    Err(syn::Error::new_spanned(value, message))
+}
+
+struct RawHook {
+    type_: syn::Ident,
+    _equals: Token![=],
+    /// The tokens between `=` and the next `->` or `,`: they should be the path of a function,
+    /// but we only check that during validation, to provide better error messages.
+    value: TokenStream2,
+    /// The pattern after `->`, if any.
+    binding: Option<Pat>,
+}
+
+impl Parse for RawArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let vars = Punctuated::<RawHook, Token![,]>::parse_terminated(input)?;
+        Ok(RawArgs {
+            vars: vars.into_iter().collect(),
+        })
+    }
+}
+
+impl Parse for RawHook {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let parsed_type: Ident = input.parse()?;
+        if input.is_empty() || input.peek(Token![,]) {
+            return Err(syn::Error::new(
+                parsed_type.span(),
+                format!("expected a hook: `{} = my_function`", parsed_type),
+            ));
+        }
+        let sep: Token![=] = input.parse()?;
+        let value = input.step(|cursor| {
+            let mut rest = *cursor;
+            let mut tokens = TokenStream2::new();
+            while let Some((token, next)) = rest.token_tree() {
+                let is_end = match &token {
+                    TokenTree::Punct(punct) if punct.as_char() == ',' => true,
+                    TokenTree::Punct(punct) if punct.as_char() == '-' => {
+                        punct.spacing() == Spacing::Joint
+                            && next.punct().is_some_and(|(next, _)| next.as_char() == '>')
+                    }
+                    _ => false,
+                };
+                if is_end {
+                    break;
+                }
+                tokens.extend([token]);
+                rest = next;
+            }
+            Ok((tokens, rest))
+        })?;
+        if value.is_empty() {
+            return Err(syn::Error::new_spanned(
+                sep,
+                format!("expected a hook after `=`: `{} = my_function`", parsed_type),
+            ));
+        }
+        let binding = if input.peek(Token![->]) {
+            input.parse::<Token![->]>()?;
+            Some(Pat::parse_single(input)?)
+        } else {
+            None
+        };
+        Ok(Self {
+            type_: parsed_type,
+            _equals: sep,
+            value,
+            binding,
+        })
+    }
+}
+
+/// The hook as written in the attribute, e.g. `super::setup`.
+fn path_to_string(path: &syn::Path) -> String {
+    let segments: Vec<String> = path
+        .segments
+        .iter()
+        .map(|segment| segment.ident.to_string())
+        .collect();
+    let prefix = if path.leading_colon.is_some() {
+        "::"
+    } else {
+        ""
+    };
+    format!("{}{}", prefix, segments.join("::"))
+}
+
+/// `#[macros03::cases]`, or `#[cases]` if it was imported.
+fn is_cases_attribute(attr: &Attribute) -> bool {
+    attr.path()
+        .segments
+        .last()
+        .is_some_and(|segment| segment.ident == "cases")
+}
+
+/// The number of tests that `#[cases]` generates, listed inline or in `#[case(...)]` attributes.
+///
+/// Invalid cases count for nothing: `#[cases]` reports them itself.
+fn count_cases(cases_attr: &Attribute, attrs: &[Attribute], sig: &syn::Signature) -> usize {
+    let args = match &cases_attr.meta {
+        syn::Meta::List(list) => list.tokens.clone(),
+        _ => TokenStream2::new(),
+    };
+    let case_attrs: Vec<Attribute> = attrs.iter().filter(|attr| is_case(attr)).cloned().collect();
+    // The suite's own `#[macros03::test]` provides any parameter left as a fixture.
+    parse_cases(args, &case_attrs, sig, "macros03::cases", true).map_or(0, |cases| cases.len())
+}
+
+/// `#[macros03::test]` or `#[::macros03::test]`.
+fn is_macros03_test(attr: &Attribute) -> bool {
+    path_to_string(attr.path()).trim_start_matches("::") == "macros03::test"
+}
+
+/// The attributes that register tests without parameters, leaving them to fixtures.
+fn registers_parameterless_tests(attr: &Attribute) -> bool {
+    matches!(
+        path_to_string(attr.path()).trim_start_matches("::"),
+        "test"
+            | "tokio::test"
+            | "gtest"
+            | "googletest::gtest"
+            | "googletest::test"
+            | "macros03::test"
+    )
+}
@@ -1,115 +1,543 @@
-use proc_macro::TokenStream;
-use quote::quote;
-use syn::parse::{Parse, ParseStream};
-use syn::punctuated::Punctuated;
-use syn::{Attribute, ItemFn, Token};
-
-#[proc_macro_attribute]
-pub fn test(args: TokenStream, input: TokenStream) -> TokenStream {
-    let test_fn: ItemFn = syn::parse_macro_input!(input as ItemFn);
-    let Args { before, after } = syn::parse_macro_input!(args as RawArgs).validate();
-
-    let ItemFn {
-        attrs,
-        vis,
-        sig,
-        block,
-    } = test_fn;
-
-    let block_stmts = block.stmts;
-
-    let mut output = quote::quote! {
-        #(#attrs)*
-        #vis #sig
-        {
-            // before callings
-            #(
-                #before();
-            )*
-
-            // original block
-            #(#block_stmts)*
-
-            // after callings
-            #(
-                #after();
-            )*
+//! A `#[test]` attribute with `before` and `after` hooks.
+//!
+//! The attribute itself lives in `macros03_impl`: a procedural macro crate can only
+//! export macros, so the types that the generated code relies on are defined here.
+//!
+//! ```rust,ignore
+//! fn setup(ctx: &macros03::TestContext) -> Result<Database, Error> {
+//!     Database::connect(ctx.name())
+//! }
+//!
+//! #[macros03::test(before = setup -> db, after = cleanup)]
+//! fn it_works() {
+//!     assert!(db.is_empty());
+//! }
+//! ```
+//!
+//! `async fn` tests can use `async` hooks too. `#[macros03::test]` must come first,
+//! so that the runtime attribute wraps the hooks as well:
+//!
+//! ```rust,ignore
+//! #[macros03::test(before = start_server -> server)]
+//! #[tokio::test]
+//! async fn it_works() {
+//!     assert!(server.is_running().await);
+//! }
+//! ```
+//!
+//! `#[suite]` attaches hooks to every test of a module. `before_all` and `after_all`
+//! run once for the whole module, even when its tests run in parallel:
+//!
+//! ```rust,ignore
+//! #[macros03::suite(before_all = start_database, after_all = stop_database, before_each = setup -> db)]
+//! mod tests {
+//!     #[test]
+//!     fn it_works() {
+//!         assert!(db.is_empty());
+//!     }
+//! }
+//! ```
+//!
+//! Tests can take parameters: each one is created by a `#[fixture]`, picked by the type
+//! of the parameter, or by name with `#[from(...)]`. Fixtures can depend on other fixtures:
+//!
+//! ```rust,ignore
+//! #[macros03::fixture]
+//! fn client(server: MockServer) -> ApiClient {
+//!     ApiClient::new(server)
+//! }
+//!
+//! #[macros03::test]
+//! fn it_works(#[from(client)] api: ApiClient) {
+//!     assert!(api.health_check().is_ok());
+//! }
+//! ```
+//!
+//! Fixtures are not shared: every parameter gets a value of its own, and so does every
+//! dependency. A test that took both `api: ApiClient` and `server: MockServer` would get
+//! a different server from the one behind `api`.
+//!
+//! `#[cases]` turns a function into one test per `#[case(...)]`:
+//!
+//! ```rust,ignore
+//! #[macros03::cases]
+//! #[case(StatusCode::MOVED_PERMANENTLY, true)]
+//! #[case::ok(StatusCode::OK, false)]
+//! #[case(StatusCode::from_u16(0).unwrap(), false; should_panic)]
+//! fn is_redirect(code: StatusCode, expected: bool) {
+//!     assert_eq!(code.is_redirection(), expected);
+//! }
+//! ```
+//!
+//! The tests are named after the function: `is_redirect_case_1_statuscode_moved_permanently_true`,
+//! `is_redirect_ok`, ... The cases can also be listed in the attribute itself:
+//! `#[macros03::cases((StatusCode::MOVED_PERMANENTLY, true), ok(StatusCode::OK, false))]`.
+use std::fmt::Debug;
+use std::future::Future;
+use std::time::Instant;
+
+pub use macros03_impl::{cases, fixture, suite, test};
+
+/// Information about the test that a hook is running for.
+#[derive(Debug, Clone)]
+pub struct TestContext {
+    name: &'static str,
+    module_path: &'static str,
+    started_at: Instant,
+}
+
+impl TestContext {
+    #[doc(hidden)]
+    pub fn new(name: &'static str, module_path: &'static str) -> Self {
+        Self {
+            name,
+            module_path,
+            started_at: Instant::now(),
         }
-    };
+    }
 
-    if !attrs.iter().any(|a| is_test_attribute(a)) {
-        output = {
-            quote! {
-                #[::core::prelude::v1::test]
-                #output
-            }
-        };
+    /// The name of the test function, e.g. `it_works`.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The path of the module the test is defined in, e.g. `my_crate::tests`.
+    pub fn module_path(&self) -> &'static str {
+        self.module_path
+    }
+
+    /// When the test started, i.e. before its first `before` hook ran.
+    pub fn started_at(&self) -> Instant {
+        self.started_at
+    }
+}
+
+/// A function that can be used as a `before` or `after` hook.
+///
+/// It either takes no arguments or a `&TestContext`.
+/// `Marker` tells the two apart, it's inferred by the compiler.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` can't be used as a hook",
+    label = "not a valid hook",
+    note = "hooks are functions that take either no arguments or a `&macros03::TestContext`"
+)]
+pub trait Hook<Marker> {
+    type Output;
+
+    fn call(self, ctx: &TestContext) -> Self::Output;
+}
+
+#[doc(hidden)]
+pub struct NoArgs;
+
+#[doc(hidden)]
+pub struct WithContext;
+
+impl<F, R> Hook<NoArgs> for F
+where
+    F: FnOnce() -> R,
+{
+    type Output = R;
+
+    fn call(self, _ctx: &TestContext) -> R {
+        self()
     }
-    output.into()
 }
 
-struct RawArgs {
-    vars: Vec<RawHook>,
+impl<F, R> Hook<WithContext> for F
+where
+    F: FnOnce(&TestContext) -> R,
+{
+    type Output = R;
+
+    fn call(self, ctx: &TestContext) -> R {
+        self(ctx)
+    }
 }
 
-/// Argument parsing goes through two phases:
+/// A function that can be used as a hook by an `async fn` test.
 ///
-/// 1. Parse the raw arguments into a struct, which is syntactically what we expect
-/// 2. Validate the arguments and convert them into the form we want (semantic validation)
-struct Args {
-    before: Vec<syn::Path>,
-    after: Vec<syn::Path>,
+/// On top of the functions accepted by [`Hook`], it can be an `async fn`:
+/// its output is awaited within the test.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` can't be used as a hook",
+    label = "not a valid hook",
+    note = "hooks are functions, `async` or not, that take either no arguments \
+            or a `&macros03::TestContext`, and return `()` or a `Result`"
+)]
+pub trait AsyncHook<Marker> {
+    type Output;
+
+    fn call(self, ctx: &TestContext) -> impl Future<Output = Self::Output>;
 }
 
-impl RawArgs {
-    pub fn validate(self) -> Args {
-        let mut before_args:Vec<syn::Path> = Vec::with_capacity(1);
-        let mut after_args:Vec<syn::Path> = Vec::with_capacity(1);
+#[doc(hidden)]
+pub struct AsyncNoArgs;
 
-        for varg in self.vars.iter() {
-            if varg.type_.eq("before") {
-                before_args.push(varg.fn_path.clone());
-            }
-            else if varg.type_.eq("after")
-            {
-                after_args.push(varg.fn_path.clone());
-            }
-        }
+#[doc(hidden)]
+pub struct AsyncWithContext;
 
-        Args{
-            before: before_args, after: after_args
-        }
+// The `HookOutput` bounds keep the impls apart: a function returning a future
+// is never mistaken for a synchronous hook, and vice versa.
+impl<F, R> AsyncHook<NoArgs> for F
+where
+    F: FnOnce() -> R,
+    R: HookOutput,
+{
+    type Output = R;
+
+    async fn call(self, _ctx: &TestContext) -> R {
+        self()
+    }
+}
+
+impl<F, R> AsyncHook<WithContext> for F
+where
+    F: FnOnce(&TestContext) -> R,
+    R: HookOutput,
+{
+    type Output = R;
+
+    async fn call(self, ctx: &TestContext) -> R {
+        self(ctx)
+    }
+}
+
+impl<F, R> AsyncHook<AsyncNoArgs> for F
+where
+    F: AsyncFnOnce() -> R,
+    R: HookOutput,
+{
+    type Output = R;
+
+    async fn call(self, _ctx: &TestContext) -> R {
+        self().await
     }
 }
 
-struct RawHook {
-    type_: syn::Ident,
-    _equals: Token![=],
-    fn_path: syn::Path,
+impl<F, R> AsyncHook<AsyncWithContext> for F
+where
+    F: AsyncFnOnce(&TestContext) -> R,
+    R: HookOutput,
+{
+    type Output = R;
+
+    async fn call(self, ctx: &TestContext) -> R {
+        self(ctx).await
+    }
+}
+
+/// What a hook can return: `()` or a `Result`.
+///
+/// If a `before` hook returns `Ok(value)`, `value` can be bound to a variable
+/// in the test body with `before = hook -> name`.
+#[diagnostic::on_unimplemented(
+    message = "hooks must return `()` or a `Result`, not `{Self}`",
+    label = "invalid return type for a hook",
+    note = "`async` hooks can only be used by `async fn` tests"
+)]
+pub trait HookOutput {
+    type Value;
+    type Error: Debug;
+
+    fn into_result(self) -> Result<Self::Value, Self::Error>;
 }
 
-impl Parse for RawArgs {
-    fn parse(input: ParseStream) -> syn::Result<Self> {
-        let vars = Punctuated::<RawHook, Token![,]>::parse_terminated(input)?;
-        Ok(RawArgs {
-            vars: vars.into_iter().collect(),
-        })
+impl HookOutput for () {
+    type Value = ();
+    type Error = std::convert::Infallible;
+
+    fn into_result(self) -> Result<(), Self::Error> {
+        Ok(())
     }
 }
 
-impl Parse for RawHook {
-    fn parse(input: ParseStream) -> syn::Result<Self> {
-        let parsed_type = input.parse()?;
-        let sep = input.parse()?;
-        let parsed_path = input.parse()?;
-        Ok(Self{type_: parsed_type, _equals: sep, fn_path:parsed_path})
+impl<T, E: Debug> HookOutput for Result<T, E> {
+    type Value = T;
+    type Error = E;
+
+    fn into_result(self) -> Result<T, E> {
+        self
     }
 }
 
-fn is_test_attribute(attr: &Attribute) -> bool {
-    let last_segment = match attr.path().segments.last() {
-        Some(last_segment) => last_segment,
-        None => return false,
-    };
-    last_segment.ident == "test"
+/// A function annotated with `#[fixture]`.
+///
+/// The attribute implements this trait for a struct named after the function:
+/// `#[from(db)]` refers to it.
+pub trait Fixture {
+    type Output;
+
+    /// Create the value, along with the fixtures it depends on.
+    ///
+    /// Each call creates new values for the dependencies too: nothing is cached.
+    fn create(ctx: &TestContext) -> Self::Output;
+}
+
+/// `Self` is created by the fixture `F`.
+///
+/// A parameter without `#[from(...)]` is created by the only fixture that returns its type.
+#[diagnostic::on_unimplemented(
+    message = "no fixture returns a `{Self}`",
+    label = "no `#[macros03::fixture]` for this parameter",
+    note = "add a `#[macros03::fixture]` function that returns a `{Self}`, \
+            or pick one by name with `#[from(my_fixture)]`"
+)]
+pub trait ProvidedBy<F: Fixture<Output = Self>> {}
+
+/// Used by the code generated by `#[test]`. Not part of the public API.
+#[doc(hidden)]
+pub mod __private {
+    use super::{AsyncHook, Fixture, Hook, HookOutput, ProvidedBy, TestContext};
+    use std::any::Any;
+    use std::future::Future;
+    use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+    use std::pin::Pin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Mutex, Once, OnceLock};
+    use std::task::{Context, Poll};
+
+    /// Return the value produced by a `before` hook, panicking if it failed.
+    ///
+    /// `path` is the hook as written in the attribute.
+    #[track_caller]
+    pub fn check_before_hook<R: HookOutput>(output: R, path: &str) -> R::Value {
+        match output.into_result() {
+            Ok(value) => value,
+            Err(e) => panic!(
+                "The `before` hook `{}` failed, the test body was skipped: {:?}",
+                path, e
+            ),
+        }
+    }
+
+    /// Why an `after` hook failed.
+    pub enum AfterFailure {
+        /// The hook panicked: its message has already been printed by the panic hook.
+        Panicked(Box<dyn Any + Send>),
+        /// The hook returned an error.
+        Failed(String),
+    }
+
+    /// Run an `after` hook, catching its failure so that the next hooks run too.
+    pub fn run_after_hook<H, M>(
+        hook: H,
+        ctx: &TestContext,
+        path: &str,
+        failure: &mut Option<AfterFailure>,
+    ) where
+        H: Hook<M>,
+        H::Output: HookOutput,
+    {
+        let output = catch_unwind(AssertUnwindSafe(|| hook.call(ctx)));
+        record_after_hook(output, path, failure);
+    }
+
+    /// Run an `after` hook of an `async fn` test, catching its failure so that
+    /// the next hooks run too.
+    pub async fn run_async_after_hook<H, M>(
+        hook: H,
+        ctx: &TestContext,
+        path: &str,
+        failure: &mut Option<AfterFailure>,
+    ) where
+        H: AsyncHook<M>,
+        H::Output: HookOutput,
+    {
+        let output = CatchUnwind::new(hook.call(ctx)).await;
+        record_after_hook(output, path, failure);
+    }
+
+    /// Only the first failure is kept in `failure`, the others are printed right away.
+    fn record_after_hook<R: HookOutput>(
+        output: std::thread::Result<R>,
+        path: &str,
+        failure: &mut Option<AfterFailure>,
+    ) {
+        let new_failure = match output.map(HookOutput::into_result) {
+            Ok(Ok(_)) => return,
+            Ok(Err(e)) => {
+                AfterFailure::Failed(format!("The `after` hook `{}` failed: {:?}", path, e))
+            }
+            Err(payload) => AfterFailure::Panicked(payload),
+        };
+        match (failure.is_some(), new_failure) {
+            (true, AfterFailure::Failed(message)) => eprintln!("{}", message),
+            (true, AfterFailure::Panicked(_)) => {}
+            (false, new_failure) => *failure = Some(new_failure),
+        }
+    }
+
+    /// Return the output of the test body, or resume its panic.
+    ///
+    /// A panic in the body takes precedence over a failing `after` hook, so that
+    /// `#[should_panic(expected = "...")]` sees the original message.
+    #[track_caller]
+    pub fn finish<T>(output: std::thread::Result<T>, after_failure: Option<AfterFailure>) -> T {
+        match (output, after_failure) {
+            (Ok(output), None) => output,
+            (Ok(_), Some(AfterFailure::Failed(message))) => panic!("{}", message),
+            (Ok(_), Some(AfterFailure::Panicked(payload))) => resume_unwind(payload),
+            (Err(payload), after_failure) => {
+                if let Some(AfterFailure::Failed(message)) = after_failure {
+                    eprintln!("{}", message);
+                }
+                resume_unwind(payload)
+            }
+        }
+    }
+
+    /// Pin down the output type of the test body, so that `?` works in `async` blocks.
+    pub fn async_body<T, F: Future<Output = T>>(body: F) -> F {
+        body
+    }
+
+    /// The `async` counterpart of `catch_unwind`: a panic while polling `F` is
+    /// turned into an `Err`.
+    pub struct CatchUnwind<F> {
+        inner: Pin<Box<F>>,
+    }
+
+    impl<F: Future> CatchUnwind<F> {
+        pub fn new(inner: F) -> Self {
+            Self {
+                inner: Box::pin(inner),
+            }
+        }
+    }
+
+    impl<F: Future> Future for CatchUnwind<F> {
+        type Output = std::thread::Result<F::Output>;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            let inner = self.inner.as_mut();
+            match catch_unwind(AssertUnwindSafe(|| inner.poll(cx))) {
+                Ok(Poll::Pending) => Poll::Pending,
+                Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+                Err(payload) => Poll::Ready(Err(payload)),
+            }
+        }
+    }
+
+    /// Create a `T` with the only fixture that returns it.
+    pub fn provide<T, F>(ctx: &TestContext) -> T
+    where
+        T: ProvidedBy<F>,
+        F: Fixture<Output = T>,
+    {
+        F::create(ctx)
+    }
+
+    /// Run a `before_all` or `after_all` hook, turning its failure into a message.
+    ///
+    /// A panic has already been reported by the panic hook, we only name the hook.
+    pub fn run_suite_hook<F, R>(hook: F, key: &str, path: &str) -> Result<(), String>
+    where
+        F: FnOnce() -> R,
+        R: HookOutput,
+    {
+        match catch_unwind(AssertUnwindSafe(hook)).map(HookOutput::into_result) {
+            Ok(Ok(_)) => Ok(()),
+            Ok(Err(e)) => Err(format!("The `{}` hook `{}` failed: {:?}", key, path, e)),
+            Err(_) => Err(format!("The `{}` hook `{}` panicked", key, path)),
+        }
+    }
+
+    /// The state shared by the tests of a module annotated with `#[suite]`.
+    ///
+    /// `before_all` runs when the first test enters the suite, `after_all` when the
+    /// last test leaves it. If some tests don't run (e.g. they were filtered out or
+    /// ignored), `after_all` runs when the test binary exits instead: libtest no longer
+    /// captures output by then, and a failure is only printed. It doesn't run at all if
+    /// the process is aborted or killed.
+    pub struct Suite {
+        n_tests: usize,
+        before_all: fn() -> Result<(), String>,
+        after_all: fn() -> Result<(), String>,
+        before_all_outcome: OnceLock<Result<(), String>>,
+        n_finished: AtomicUsize,
+        after_all_done: Once,
+    }
+
+    impl Suite {
+        pub const fn new(
+            n_tests: usize,
+            before_all: fn() -> Result<(), String>,
+            after_all: fn() -> Result<(), String>,
+        ) -> Self {
+            Self {
+                n_tests,
+                before_all,
+                after_all,
+                before_all_outcome: OnceLock::new(),
+                n_finished: AtomicUsize::new(0),
+                after_all_done: Once::new(),
+            }
+        }
+
+        /// Run `before_all` if it hasn't run yet, then panic if it failed.
+        ///
+        /// The other tests wait for the first one to be done with `before_all`.
+        #[track_caller]
+        pub fn enter(&'static self) {
+            let outcome = self.before_all_outcome.get_or_init(|| {
+                run_at_exit(self);
+                (self.before_all)()
+            });
+            if let Err(e) = outcome {
+                panic!("{}, the test body was skipped", e);
+            }
+        }
+
+        /// Run `after_all` if this is the last test of the suite, panicking if it failed.
+        #[track_caller]
+        pub fn leave(&'static self) {
+            if self.n_finished.fetch_add(1, Ordering::SeqCst) + 1 == self.n_tests {
+                if let Err(e) = self.run_after_all() {
+                    panic!("{}", e);
+                }
+            }
+        }
+
+        /// `after_all` only runs once, and only if `before_all` did.
+        fn run_after_all(&self) -> Result<(), String> {
+            let mut outcome = Ok(());
+            self.after_all_done.call_once(|| {
+                if self.before_all_outcome.get().is_some() {
+                    outcome = (self.after_all)();
+                }
+            });
+            outcome
+        }
+    }
+
+    static PENDING_SUITES: Mutex<Vec<&'static Suite>> = Mutex::new(Vec::new());
+
+    extern "C" {
+        fn atexit(callback: extern "C" fn()) -> std::ffi::c_int;
+    }
+
+    /// Make sure that `suite` runs its `after_all` hook before the process exits.
+    fn run_at_exit(suite: &'static Suite) {
+        static REGISTER: Once = Once::new();
+        REGISTER.call_once(|| {
+            // SAFETY: `at_exit` doesn't unwind, `run_after_all` catches panics.
+            unsafe {
+                atexit(at_exit);
+            }
+        });
+        PENDING_SUITES
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(suite);
+    }
+
+    extern "C" fn at_exit() {
+        let suites = std::mem::take(&mut *PENDING_SUITES.lock().unwrap_or_else(|e| e.into_inner()));
+        for suite in suites {
+            if let Err(e) = suite.run_after_all() {
+                eprintln!("{}", e);
+            }
+        }
+    }
 }
@@ -0,0 +1,57 @@
+use macros03::{cases, fixture, TestContext};
+
+fn is_redirect(code: u16) -> bool {
+    assert!((100..600).contains(&code), "Invalid status code: {}", code);
+    (300..400).contains(&code)
+}
+
+#[cases]
+#[case(301, true)]
+#[case(200, false)]
+#[case::not_found(404, false)]
+#[case(0, false; should_panic(expected = "Invalid status code: 0"))]
+#[case(600, false; ignore = "not a valid status code")]
+fn redirects(code: u16, expected: bool) {
+    assert_eq!(is_redirect(code), expected);
+}
+
+#[cases((301, true), see_other(303, true))]
+#[case::ok(200, false)]
+fn redirects_listed_inline(code: u16, expected: bool) {
+    assert_eq!(is_redirect(code), expected);
+}
+
+mod nested {
+    use macros03::cases;
+
+    // The body is not moved into a module of its own: `super::` still means the
+    // parent of `nested`.
+    #[cases((302, true))]
+    fn redirects(code: u16, expected: bool) {
+        assert_eq!(super::is_redirect(code), expected);
+    }
+}
+
+fn hello() {
+    println!("Hello, world!");
+}
+
+#[fixture]
+fn test_name(ctx: &TestContext) -> String {
+    ctx.name().to_string()
+}
+
+// The arguments bind the first parameters, `#[macros03::test]` provides the others.
+#[cases]
+#[case::localhost("127.0.0.1")]
+#[case::ipv6("::1")]
+#[macros03::test(before = hello)]
+#[tokio::test]
+async fn cases_with_hooks_and_fixtures(host: &str, name: String) {
+    tokio::task::yield_now().await;
+    assert!(!host.is_empty());
+    assert!(
+        name == "cases_with_hooks_and_fixtures_localhost"
+            || name == "cases_with_hooks_and_fixtures_ipv6"
+    );
+}
@@ -0,0 +1,128 @@
+use macros03::{fixture, TestContext};
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+thread_local! {
+    static DROPPED: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+fn dropped(what: String) {
+    DROPPED.with(|log| log.borrow_mut().push(what));
+}
+
+struct Server {
+    port: u16,
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        dropped(format!("server {}", self.port));
+    }
+}
+
+struct Connection {
+    server: Server,
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        dropped(format!("connection to {}", self.server.port));
+    }
+}
+
+struct Logger {
+    prefix: String,
+}
+
+impl Drop for Logger {
+    fn drop(&mut self) {
+        dropped(format!("logger of {}", self.prefix));
+    }
+}
+
+#[fixture]
+fn server() -> Server {
+    Server { port: 5432 }
+}
+
+#[fixture]
+fn other_server() -> Server {
+    Server { port: 5433 }
+}
+
+#[fixture]
+fn connection(#[from(server)] server: Server) -> Connection {
+    Connection { server }
+}
+
+#[fixture]
+fn logger(ctx: &TestContext) -> Logger {
+    Logger {
+        prefix: ctx.name().to_string(),
+    }
+}
+
+struct Ticket(usize);
+
+struct Queue {
+    first: Ticket,
+}
+
+#[fixture]
+fn ticket() -> Ticket {
+    static N_TICKETS: AtomicUsize = AtomicUsize::new(0);
+    Ticket(N_TICKETS.fetch_add(1, Ordering::SeqCst))
+}
+
+#[fixture]
+fn queue(first: Ticket) -> Queue {
+    Queue { first }
+}
+
+#[macros03::test]
+fn fixtures_by_type(connection: Connection, logger: Logger) {
+    assert_eq!(connection.server.port, 5432);
+    assert_eq!(logger.prefix, "fixtures_by_type");
+}
+
+#[macros03::test]
+fn fixtures_by_name(#[from(server)] a: Server, #[from(other_server)] b: Server) {
+    assert_eq!((a.port, b.port), (5432, 5433));
+}
+
+#[macros03::test]
+fn dependencies_are_not_shared(queue: Queue, ticket: Ticket) {
+    assert_ne!(queue.first.0, ticket.0);
+}
+
+#[macros03::test]
+#[should_panic(expected = "Panic in the body")]
+fn panicking_body(logger: Logger, connection: Connection) {
+    assert_eq!(logger.prefix, "panicking_body");
+    assert_eq!(connection.server.port, 5432);
+    panic!("Panic in the body");
+}
+
+#[test]
+fn fixtures_are_dropped_in_reverse_order_after_a_panic() {
+    DROPPED.with(|log| log.borrow_mut().clear());
+    assert!(std::panic::catch_unwind(panicking_body).is_err());
+    DROPPED.with(|log| {
+        assert_eq!(
+            *log.borrow(),
+            [
+                "connection to 5432",
+                "server 5432",
+                "logger of panicking_body"
+            ]
+        )
+    });
+}
+
+#[macros03::test]
+#[tokio::test]
+async fn async_fixtures(ctx: &TestContext, connection: Connection) {
+    tokio::task::yield_now().await;
+    assert_eq!(ctx.name(), "async_fixtures");
+    assert_eq!(connection.server.port, 5432);
+}
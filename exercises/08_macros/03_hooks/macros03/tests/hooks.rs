@@ -0,0 +1,143 @@
+use macros03::TestContext;
+use std::cell::RefCell;
+
+fn check_context(ctx: &TestContext) {
+    assert_eq!(ctx.name(), "with_context");
+    assert_eq!(ctx.module_path(), "hooks");
+}
+
+fn connect() -> Result<u16, String> {
+    Ok(5432)
+}
+
+fn unreachable_database() -> Result<u16, String> {
+    Err("Connection refused".into())
+}
+
+fn flush(ctx: &TestContext) -> Result<(), String> {
+    Err(format!("Nothing to flush for `{}`", ctx.name()))
+}
+
+#[macros03::test(before = check_context)]
+fn with_context() {}
+
+#[macros03::test(before = connect -> port)]
+fn binding() {
+    assert_eq!(port, 5432);
+}
+
+#[macros03::test(before = unreachable_database -> port)]
+#[should_panic(
+    expected = "The `before` hook `unreachable_database` failed, the test body was skipped: \"Connection refused\""
+)]
+fn failing_before() {
+    assert_eq!(port, 5432);
+}
+
+#[macros03::test(after = flush)]
+#[should_panic(
+    expected = "The `after` hook `flush` failed: \"Nothing to flush for `failing_after`\""
+)]
+fn failing_after() {}
+
+thread_local! {
+    static CLEANED_UP: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+fn cleanup(ctx: &TestContext) {
+    CLEANED_UP.with(|log| log.borrow_mut().push(ctx.name().to_string()));
+}
+
+fn cleaned_up() -> Vec<String> {
+    CLEANED_UP.with(|log| log.take())
+}
+
+fn panic_in_after() {
+    panic!("Panic in the `after` hook");
+}
+
+#[macros03::test(after = cleanup)]
+#[should_panic(expected = "Panic in the body")]
+fn panicking_body() {
+    panic!("Panic in the body");
+}
+
+#[macros03::test(after = cleanup)]
+#[ignore = "called by `after_hooks_run_on_early_returns`"]
+fn early_return() -> Result<(), String> {
+    Err("Early return".to_string())?;
+    unreachable!()
+}
+
+#[test]
+fn after_hooks_run_when_the_body_panics() {
+    cleaned_up();
+    assert!(std::panic::catch_unwind(panicking_body).is_err());
+    assert_eq!(cleaned_up(), ["panicking_body"]);
+}
+
+#[test]
+fn after_hooks_run_on_early_returns() {
+    cleaned_up();
+    assert_eq!(early_return(), Err("Early return".to_string()));
+    assert_eq!(cleaned_up(), ["early_return"]);
+}
+
+#[macros03::test(after = panic_in_after)]
+#[should_panic(expected = "Panic in the body")]
+fn should_panic_sees_the_panic_of_the_body() {
+    panic!("Panic in the body");
+}
+
+async fn async_connect(ctx: &TestContext) -> Result<u16, String> {
+    tokio::task::yield_now().await;
+    assert_eq!(ctx.name(), "async_hooks");
+    Ok(5432)
+}
+
+async fn async_cleanup(ctx: &TestContext) {
+    tokio::task::yield_now().await;
+    cleanup(ctx);
+}
+
+#[macros03::test(before = async_connect -> port, after = async_cleanup)]
+#[tokio::test]
+async fn async_hooks() {
+    assert_eq!(port, 5432);
+}
+
+#[macros03::test(before = connect -> port, after = async_cleanup)]
+#[tokio::test]
+#[should_panic(expected = "Panic in the async body")]
+async fn panicking_async_body() {
+    assert_eq!(port, 5432);
+    panic!("Panic in the async body");
+}
+
+#[test]
+fn async_after_hooks_run_when_the_body_panics() {
+    cleaned_up();
+    assert!(std::panic::catch_unwind(panicking_async_body).is_err());
+    assert_eq!(cleaned_up(), ["panicking_async_body"]);
+}
+
+#[macros03::test(before = connect -> port, after = cleanup)]
+#[googletest::gtest]
+#[tokio::test]
+async fn with_gtest() {
+    googletest::expect_that!(port, googletest::prelude::eq(5432));
+}
+
+// `#[sqlx::test]` needs a database: we only check that it composes with the hooks,
+// providing the pool itself rather than leaving it to fixtures.
+#[macros03::test(before = connect -> port, after = cleanup)]
+#[sqlx::test]
+#[ignore]
+async fn with_sqlx(pool: sqlx::PgPool) {
+    let row: (i32,) = sqlx::query_as("SELECT $1")
+        .bind(i32::from(port))
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(row.0, 5432);
+}
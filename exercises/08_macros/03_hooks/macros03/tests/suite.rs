@@ -0,0 +1,143 @@
+#[macros03::suite(
+    before_all = start_server,
+    after_all = stop_server,
+    before_each = connect -> port,
+    after_each = disconnect
+)]
+mod suite {
+    use macros03::TestContext;
+    use std::cell::RefCell;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static N_STARTS: AtomicUsize = AtomicUsize::new(0);
+
+    thread_local! {
+        static CALLS: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn start_server() {
+        N_STARTS.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn stop_server() {
+        println!("Stopping the server");
+    }
+
+    fn connect() -> Result<u16, String> {
+        assert_eq!(N_STARTS.load(Ordering::SeqCst), 1);
+        CALLS.with(|calls| calls.borrow_mut().push("connect"));
+        Ok(5432)
+    }
+
+    fn disconnect(ctx: &TestContext) {
+        println!("Disconnecting `{}`", ctx.name());
+    }
+
+    fn hello() {
+        CALLS.with(|calls| calls.borrow_mut().push("hello"));
+    }
+
+    #[test]
+    fn first() {
+        assert_eq!(port, 5432);
+    }
+
+    #[test]
+    fn second() {
+        assert_eq!(port, 5432);
+    }
+
+    #[macros03::test(before = hello)]
+    fn the_test_hooks_run_closer_to_the_body() {
+        assert_eq!(port, 5432);
+        CALLS.with(|calls| assert_eq!(*calls.borrow(), ["connect", "hello"]));
+    }
+
+    #[tokio::test]
+    async fn async_test() {
+        assert_eq!(port, 5432);
+    }
+
+    #[test]
+    #[should_panic(expected = "Panic in the body")]
+    fn panicking_test() {
+        assert_eq!(port, 5432);
+        panic!("Panic in the body");
+    }
+}
+
+#[macros03::suite(before_all = unreachable_server)]
+mod failing_suite {
+    fn unreachable_server() -> Result<(), String> {
+        Err("Connection refused".into())
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "The `before_all` hook `unreachable_server` failed: \"Connection refused\", the test body was skipped"
+    )]
+    fn first() {}
+
+    #[test]
+    #[should_panic(
+        expected = "The `before_all` hook `unreachable_server` failed: \"Connection refused\", the test body was skipped"
+    )]
+    fn second() {}
+}
+
+#[macros03::suite(after_all = stop_server)]
+mod suite_with_cases {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    static STOPPED: AtomicBool = AtomicBool::new(false);
+
+    fn stop_server() {
+        STOPPED.store(true, Ordering::SeqCst);
+        println!("Stopping the server of the cases");
+    }
+
+    #[macros03::cases((2), (3), (4))]
+    #[case(1)]
+    fn cases(n: u32) {
+        assert!(
+            !STOPPED.load(Ordering::SeqCst),
+            "`after_all` ran before case {}",
+            n
+        );
+    }
+}
+
+/// When a filter leaves out some tests of a suite, `after_all` runs as the test binary
+/// exits, after libtest has printed its summary.
+#[test]
+fn after_all_runs_at_exit_when_tests_are_filtered() {
+    let output = std::process::Command::new(std::env::current_exe().unwrap())
+        .args(["suite::first", "--exact"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{}", stdout);
+    let (tests, after_exit) = stdout.split_once("test result: ok. 1 passed").unwrap();
+    assert!(tests.contains("test suite::first ... ok"), "{}", stdout);
+    assert!(!tests.contains("Stopping the server"), "{}", stdout);
+    assert!(after_exit.contains("Stopping the server"), "{}", stdout);
+}
+
+/// `after_all` waits for every case, whether it's listed in `#[cases(...)]` or in a
+/// `#[case(...)]` attribute. Running them one at a time makes an early `after_all` fail
+/// the next case.
+#[test]
+fn after_all_runs_after_every_case() {
+    let output = std::process::Command::new(std::env::current_exe().unwrap())
+        .args(["suite_with_cases::", "--test-threads=1", "--nocapture"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{}", stdout);
+    let (tests, _) = stdout.split_once("test result: ok. 4 passed").unwrap();
+    assert!(
+        tests.contains("Stopping the server of the cases"),
+        "{}",
+        stdout
+    );
+}
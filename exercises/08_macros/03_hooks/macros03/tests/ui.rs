@@ -0,0 +1,8 @@
+/// Lock in the error messages of `#[macros03::test]`.
+///
+/// Run with `TRYBUILD=overwrite` to update the expected output after changing a message.
+#[test]
+fn ui() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
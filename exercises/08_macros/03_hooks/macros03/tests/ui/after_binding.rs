@@ -0,0 +1,8 @@
+fn connect() -> Result<u16, String> {
+    Ok(5432)
+}
+
+#[macros03::test(after = connect -> port)]
+fn bound() {}
+
+fn main() {}
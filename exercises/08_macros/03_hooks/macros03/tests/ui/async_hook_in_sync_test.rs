@@ -0,0 +1,12 @@
+// `#[test]` functions are stripped outside of `cargo test`, so we call the hook
+// the way the code generated by `#[macros03::test(before = connect -> port)]` does.
+use macros03::{Hook, TestContext};
+
+async fn connect() -> Result<u16, String> {
+    Ok(5432)
+}
+
+fn main() {
+    let ctx = TestContext::new("sync_test", module_path!());
+    let _port = macros03::__private::check_before_hook(Hook::call(connect, &ctx), "connect");
+}
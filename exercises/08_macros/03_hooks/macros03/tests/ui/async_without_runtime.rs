@@ -0,0 +1,4 @@
+#[macros03::test]
+async fn no_runtime() {}
+
+fn main() {}
@@ -0,0 +1,36 @@
+use macros03::cases;
+
+#[cases(1, 2)]
+#[case(1)]
+fn unparenthesized_cases(n: u32) {}
+
+#[cases((1), (2, 3))]
+fn wrong_number_of_inline_arguments(n: u32) {}
+
+#[cases((1), same(2))]
+#[case::same(3)]
+fn duplicate_inline_names(n: u32) {}
+
+#[cases]
+fn no_cases(n: u32) {}
+
+#[cases]
+#[case(1, 2)]
+#[case()]
+fn wrong_number_of_arguments(n: u32) {}
+
+#[cases]
+#[case(1; should_panik)]
+#[case(2; timeout = 5)]
+fn unknown_option(n: u32) {}
+
+#[cases]
+#[case::same(1)]
+#[case::same(2)]
+fn duplicate_names(n: u32) {}
+
+#[cases]
+#[case(1)]
+async fn no_runtime(n: u32) {}
+
+fn main() {}
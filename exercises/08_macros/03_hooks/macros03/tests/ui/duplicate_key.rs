@@ -0,0 +1,8 @@
+fn setup() {}
+
+fn more_setup() {}
+
+#[macros03::test(before = setup, before = more_setup)]
+fn twice() {}
+
+fn main() {}
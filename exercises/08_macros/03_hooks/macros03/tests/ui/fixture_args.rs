@@ -0,0 +1,28 @@
+use macros03::fixture;
+
+struct Server;
+
+#[fixture(scope = module)]
+fn with_arguments() -> Server {
+    Server
+}
+
+#[fixture]
+async fn asynchronous() -> Server {
+    Server
+}
+
+#[fixture]
+fn generic<T: Default>() -> T {
+    T::default()
+}
+
+#[fixture]
+fn nothing() {}
+
+#[fixture]
+fn from_a_string(#[from("server")] server: Server) -> Server {
+    server
+}
+
+fn main() {}
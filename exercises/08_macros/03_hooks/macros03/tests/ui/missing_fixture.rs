@@ -0,0 +1,14 @@
+use macros03::fixture;
+
+struct Server;
+
+struct Database {
+    _server: Server,
+}
+
+#[fixture]
+fn database(server: Server) -> Database {
+    Database { _server: server }
+}
+
+fn main() {}
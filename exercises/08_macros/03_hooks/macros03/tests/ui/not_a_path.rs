@@ -0,0 +1,21 @@
+fn setup() {}
+
+#[macros03::test(before = "setup")]
+fn string() {}
+
+#[macros03::test(before = setup())]
+fn call() {}
+
+#[macros03::test(before = || println!("setup"))]
+fn closure() {}
+
+#[macros03::test(before = 42)]
+fn literal() {}
+
+#[macros03::test(before)]
+fn missing() {}
+
+#[macros03::test(after = )]
+fn empty() {}
+
+fn main() {}
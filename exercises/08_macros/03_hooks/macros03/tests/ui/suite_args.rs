@@ -0,0 +1,12 @@
+fn setup() {}
+
+#[macros03::suite(before = setup)]
+mod unknown_key {}
+
+#[macros03::suite(before_al = setup)]
+mod typo {}
+
+#[macros03::suite(before_all = setup -> db)]
+mod binding {}
+
+fn main() {}
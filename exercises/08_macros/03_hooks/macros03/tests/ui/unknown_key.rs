@@ -0,0 +1,9 @@
+fn setup() {}
+
+#[macros03::test(befor = setup)]
+fn typo() {}
+
+#[macros03::test(setup = setup)]
+fn unrelated() {}
+
+fn main() {}
@@ -0,0 +1,10 @@
+// `#[test]` functions are stripped outside of `cargo test`, so we call the hook
+// the way the code generated by `#[macros03::test(before = setup)]` does.
+use macros03::{Hook, TestContext};
+
+fn setup(_a: u32, _b: u32) {}
+
+fn main() {
+    let ctx = TestContext::new("too_many_arguments", module_path!());
+    Hook::call(setup, &ctx);
+}
@@ -0,0 +1,362 @@
+//! The expansion of `#[cases]`, shared by `macros02` and `macros03`.
+//!
+//! A procedural macro crate can only export macros: the code that both attributes run
+//! lives in this regular library, and each procedural macro crate calls [`expand`].
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, ToTokens};
+use syn::parse::ParseStream;
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{Attribute, Expr, FnArg, Ident, ItemFn, PatType, Token};
+
+/// Turn `test_fn` into one test per case.
+///
+/// `attribute` is the attribute being expanded, e.g. `macros03::cases`, as shown in
+/// error messages. A case can leave out the last parameters if one of the other
+/// attributes of the function provides them as fixtures, according to `provides_fixtures`.
+pub fn expand(
+    args: TokenStream,
+    test_fn: ItemFn,
+    attribute: &str,
+    provides_fixtures: fn(&Attribute) -> bool,
+) -> syn::Result<TokenStream> {
+    let ItemFn {
+        attrs,
+        vis: _,
+        sig,
+        block,
+    } = test_fn;
+
+    let (case_attrs, attrs): (Vec<Attribute>, Vec<Attribute>) =
+        attrs.into_iter().partition(is_case);
+    let takes_fixtures = attrs.iter().any(provides_fixtures);
+    let cases = parse_cases(args, &case_attrs, &sig, attribute, takes_fixtures)?;
+    let is_async = sig.asyncness.is_some();
+    let has_test_attribute = attrs.iter().any(is_test_attribute);
+    if is_async && !has_test_attribute {
+        return Err(syn::Error::new_spanned(
+            sig.asyncness,
+            format!(
+                "`async` tests need a runtime: add `#[tokio::test]` (or a similar attribute) \
+                below `#[{}]`",
+                attribute
+            ),
+        ));
+    }
+
+    // The body is copied into every test, after binding the arguments to the parameters:
+    // it can still use the values bound by `before` hooks. Any parameter left is a fixture.
+    // The tests are emitted in place of the function, rather than in a module of their
+    // own, so that paths in the body (`super::...`) keep pointing where they did.
+    let name = &sig.ident;
+    let test_attribute = (!has_test_attribute).then(|| quote! { #[::core::prelude::v1::test] });
+    let tests = cases.into_iter().map(|case| {
+        let Case {
+            name: case_name,
+            args,
+            options,
+        } = case;
+        let mut sig = sig.clone();
+        sig.ident = Ident::new(&format!("{}_{}", name, case_name), case_name.span());
+        let mut inputs = std::mem::take(&mut sig.inputs).into_iter();
+        // `args` goes first: `zip` would otherwise take one parameter too many.
+        let bindings: Vec<TokenStream> = args
+            .into_iter()
+            .zip(inputs.by_ref())
+            .map(|(arg, input)| match input {
+                FnArg::Typed(PatType { pat, ty, .. }) => quote! { let #pat: #ty = #arg; },
+                FnArg::Receiver(receiver) => {
+                    syn::Error::new_spanned(receiver, "tests can't take `self`").to_compile_error()
+                }
+            })
+            .collect();
+        sig.inputs = inputs.collect();
+        quote! {
+            #test_attribute
+            #(#attrs)*
+            #(#[#options])*
+            #sig {
+                #(#bindings)*
+                #block
+            }
+        }
+    });
+
+    Ok(quote! {
+        #(#tests)*
+    })
+}
+
+/// A single case.
+pub struct Case {
+    name: Ident,
+    args: Vec<Expr>,
+    /// `should_panic` or `ignore`, if set.
+    options: Vec<syn::Meta>,
+}
+
+/// A case, as written in `#[cases(...)]` or in a `#[case(...)]` attribute.
+struct RawCase {
+    /// The name given to the case, if any: `not_found` in `#[case::not_found(404)]`.
+    name: Option<Ident>,
+    /// Where to point at when the case needs a generated name.
+    span: Span,
+    args: Vec<Expr>,
+    options: Vec<syn::Meta>,
+}
+
+/// The cases listed in `#[cases(...)]`, followed by the `#[case(...)]` attributes.
+///
+/// If `takes_fixtures`, a case can leave out the last parameters. `attribute` is the same
+/// as in [`expand`].
+pub fn parse_cases(
+    args: TokenStream,
+    case_attrs: &[Attribute],
+    sig: &syn::Signature,
+    attribute: &str,
+    takes_fixtures: bool,
+) -> syn::Result<Vec<Case>> {
+    let mut raw_cases = Vec::with_capacity(case_attrs.len());
+    if !args.is_empty() {
+        let parser = |input: ParseStream| parse_inline_cases(input, sig, takes_fixtures);
+        raw_cases.extend(
+            syn::parse::Parser::parse2(parser, args)?
+                .into_iter()
+                .map(Ok),
+        );
+    }
+    raw_cases.extend(case_attrs.iter().map(|attr| {
+        attr.parse_args_with(|input: ParseStream| parse_case(input, sig, takes_fixtures))
+            .map(|(args, options)| RawCase {
+                name: attr
+                    .path()
+                    .segments
+                    .iter()
+                    .nth(1)
+                    .map(|segment| segment.ident.clone()),
+                span: attr.span(),
+                args,
+                options,
+            })
+    }));
+    if raw_cases.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &sig.ident,
+            format!(
+                "no cases: list them in `#[cases(...)]`, or add a `#[case(...)]` attribute \
+                below `#[{}]` for each case",
+                attribute
+            ),
+        ));
+    }
+    let n_digits = raw_cases.len().to_string().len();
+    let mut cases = Vec::with_capacity(raw_cases.len());
+    let mut errors: Option<syn::Error> = None;
+    for (i, raw_case) in raw_cases.into_iter().enumerate() {
+        let case = raw_case.and_then(|raw_case| {
+            let name = match raw_case.name {
+                Some(name) => name,
+                None => Ident::new(&case_name(i + 1, n_digits, &raw_case.args), raw_case.span),
+            };
+            if let Some(previous) = cases.iter().find(|case: &&Case| case.name == name) {
+                let mut error = syn::Error::new(
+                    name.span(),
+                    format!("there's already a case named `{}`", name),
+                );
+                error.combine(syn::Error::new(previous.name.span(), "first defined here"));
+                return Err(error);
+            }
+            Ok(Case {
+                name,
+                args: raw_case.args,
+                options: raw_case.options,
+            })
+        });
+        match (case, &mut errors) {
+            (Ok(case), _) => cases.push(case),
+            (Err(e), Some(errors)) => errors.combine(e),
+            (Err(e), None) => errors = Some(e),
+        }
+    }
+    match errors {
+        Some(errors) => Err(errors),
+        None => Ok(cases),
+    }
+}
+
+/// `(301, true), not_found(404, false)`: the cases listed in `#[cases(...)]`, each in
+/// parentheses and optionally preceded by its name.
+fn parse_inline_cases(
+    input: ParseStream,
+    sig: &syn::Signature,
+    takes_fixtures: bool,
+) -> syn::Result<Vec<RawCase>> {
+    let mut cases = Vec::new();
+    while !input.is_empty() {
+        let name: Option<Ident> = input.parse()?;
+        if !input.peek(syn::token::Paren) {
+            return Err(input.error(
+                "expected a case in parentheses, e.g. `(301, true)` or `moved(301, true)`",
+            ));
+        }
+        let content;
+        let parens = syn::parenthesized!(content in input);
+        let (args, options) = parse_case(&content, sig, takes_fixtures)?;
+        cases.push(RawCase {
+            name,
+            span: parens.span.join(),
+            args,
+            options,
+        });
+        if input.is_empty() {
+            break;
+        }
+        input.parse::<Token![,]>()?;
+    }
+    Ok(cases)
+}
+
+/// `301, true; should_panic`: the arguments, then the options.
+fn parse_case(
+    input: ParseStream,
+    sig: &syn::Signature,
+    takes_fixtures: bool,
+) -> syn::Result<(Vec<Expr>, Vec<syn::Meta>)> {
+    let mut args = Vec::with_capacity(sig.inputs.len());
+    while !input.is_empty() && !input.peek(Token![;]) {
+        args.push(input.parse::<Expr>()?);
+        if input.is_empty() || input.peek(Token![;]) {
+            break;
+        }
+        input.parse::<Token![,]>()?;
+    }
+    let n_params = sig.inputs.len();
+    if args.len() > n_params || (args.len() < n_params && !takes_fixtures) {
+        let span = match args.get(n_params) {
+            Some(extra) => extra.span(),
+            None => input.span(),
+        };
+        let plural = if n_params == 1 { "" } else { "s" };
+        return Err(syn::Error::new(
+            span,
+            format!(
+                "expected {} argument{}, one for each parameter of `{}`, found {}",
+                n_params,
+                plural,
+                sig.ident,
+                args.len()
+            ),
+        ));
+    }
+
+    let mut options = Vec::new();
+    if input.parse::<Option<Token![;]>>()?.is_some() {
+        let metas = Punctuated::<syn::Meta, Token![,]>::parse_terminated(input)?;
+        for meta in metas {
+            let Some(key) = meta.path().get_ident() else {
+                return Err(syn::Error::new_spanned(
+                    meta.path(),
+                    "expected `should_panic` or `ignore`",
+                ));
+            };
+            if key != "should_panic" && key != "ignore" {
+                return Err(unknown_key_error(key, &["should_panic", "ignore"]));
+            }
+            options.push(meta);
+        }
+    }
+    Ok((args, options))
+}
+
+/// `case_1_301_true`: the arguments make the name readable, the index keeps it unique
+/// and sorted.
+fn case_name(index: usize, n_digits: usize, args: &[Expr]) -> String {
+    const MAX_LENGTH: usize = 40;
+
+    let mut description = String::new();
+    for c in args
+        .iter()
+        .map(|arg| arg.to_token_stream().to_string())
+        .collect::<Vec<_>>()
+        .join("_")
+        .chars()
+    {
+        if c.is_ascii_alphanumeric() {
+            description.push(c.to_ascii_lowercase());
+        } else if !description.ends_with('_') {
+            description.push('_');
+        }
+    }
+    let mut description = description.trim_matches('_').to_string();
+    description.truncate(MAX_LENGTH);
+    let description = description.trim_end_matches('_');
+
+    if description.is_empty() {
+        format!("case_{:0width$}", index, width = n_digits)
+    } else {
+        format!("case_{:0width$}_{}", index, description, width = n_digits)
+    }
+}
+
+/// `#[case(...)]` or `#[case::name(...)]`.
+pub fn is_case(attr: &Attribute) -> bool {
+    attr.path()
+        .segments
+        .first()
+        .is_some_and(|segment| segment.ident == "case")
+}
+
+/// `#[test]`, `#[tokio::test]`, `#[gtest]`, ...
+pub fn is_test_attribute(attr: &Attribute) -> bool {
+    let last_segment = match attr.path().segments.last() {
+        Some(last_segment) => last_segment,
+        None => return false,
+    };
+    last_segment.ident == "test" || last_segment.ident == "gtest"
+}
+
+/// `before`, `after` or `before_all`, for example.
+pub fn list_of_keys(keys: &[&str], conjunction: &str) -> String {
+    let quoted: Vec<String> = keys.iter().map(|key| format!("`{}`", key)).collect();
+    match quoted.split_last() {
+        Some((last, [])) => last.clone(),
+        Some((last, rest)) => format!("{} {} {}", rest.join(", "), conjunction, last),
+        None => String::new(),
+    }
+}
+
+/// `key` is not one of `keys`: suggest the closest one, if it's close enough.
+pub fn unknown_key_error(key: &Ident, keys: &[&str]) -> syn::Error {
+    let name = key.to_string();
+    let suggestion = keys
+        .iter()
+        .map(|candidate| (candidate, edit_distance(candidate, &name.to_lowercase())))
+        .filter(|(_, distance)| *distance <= 2)
+        .min_by_key(|(_, distance)| *distance);
+    let message = match suggestion {
+        Some((candidate, _)) => {
+            format!("unknown argument `{}`, did you mean `{}`?", name, candidate)
+        }
+        None => format!(
+            "unknown argument `{}`, expected {}",
+            name,
+            list_of_keys(keys, "or")
+        ),
+    };
+    syn::Error::new(key.span(), message)
+}
+
+/// The number of single-character edits needed to turn `a` into `b` (Levenshtein distance).
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}